@@ -1,14 +1,103 @@
 use clap::{Parser, Subcommand};
 use colored::Colorize;
-use smbuilder::prelude::{callback_types::LogType, *};
-use std::{fmt::format, path::PathBuf, thread};
+use smbuilder::prelude::{callback_types::LogType, diagnostic_types::*, *};
+use std::{
+    fmt::format,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
 
 use smbuilder_cli::get_builder;
 
 #[derive(Clone, Subcommand)]
 enum Action {
-    Build { spec_path: PathBuf },
-    Run { spec_path: PathBuf },
+    Build {
+        spec_path: PathBuf,
+        /// A yaml file with extra rules
+        /// to diagnose build failures with.
+        #[arg(long)]
+        hints: Option<PathBuf>,
+        /// Print what the build would
+        /// do, without doing it.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    BuildAll {
+        spec_paths: Vec<PathBuf>,
+        /// The amount of compile jobs that
+        /// all of the builds share.
+        #[arg(long)]
+        cpus: Option<usize>,
+        /// The amount of specs
+        /// to compile at once.
+        #[arg(long)]
+        concurrent: Option<usize>,
+    },
+    Run {
+        spec_path: PathBuf,
+    },
+    Doctor {
+        spec_path: Option<PathBuf>,
+    },
+    History {
+        spec_path: PathBuf,
+        /// The build to show the
+        /// stages and the log of.
+        id: Option<String>,
+    },
+    InspectTextures {
+        spec_path: PathBuf,
+        /// The texture pack to check, instead
+        /// of the ones in the spec.
+        pack: Option<PathBuf>,
+    },
+    InspectDynosPacks {
+        spec_path: PathBuf,
+        /// The DynOS pack to check, instead
+        /// of the ones in the spec.
+        pack: Option<PathBuf>,
+    },
+    Mods {
+        spec_path: PathBuf,
+        /// The mod to enable, named after
+        /// its file or directory.
+        #[arg(long)]
+        enable: Option<String>,
+        /// The mod to disable, named after
+        /// its file or directory.
+        #[arg(long)]
+        disable: Option<String>,
+    },
+    DynosPacks {
+        spec_path: PathBuf,
+        /// The pack to enable, named
+        /// after its directory.
+        #[arg(long)]
+        enable: Option<String>,
+        /// The pack to disable, named
+        /// after its directory.
+        #[arg(long)]
+        disable: Option<String>,
+        /// The order of the packs, highest
+        /// priority first, separated by commas.
+        #[arg(long, value_delimiter = ',')]
+        order: Option<Vec<String>>,
+    },
+    GcPackStore {
+        /// The directory of the store,
+        /// as in `pack_store` in a spec.
+        store_dir: PathBuf,
+    },
+    Clean {
+        spec_path: PathBuf,
+    },
+    Distclean {
+        spec_path: PathBuf,
+    },
+    Reset {
+        spec_path: PathBuf,
+    },
 }
 
 #[derive(Parser)]
@@ -18,16 +107,67 @@ struct Args {
     subcommand: Action,
 }
 
-fn build(base_dir: PathBuf, callbacks: Callbacks<'static>) {
+fn plan(base_dir: PathBuf, callbacks: Callbacks<'static>) {
+    let mut builder = get_builder(base_dir, callbacks);
+
+    let steps = match builder.plan() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for step in steps {
+        match step {
+            PlanStep::Setup(_) | PlanStep::Compile | PlanStep::PostBuild(_) => {
+                println!("{}", step.to_string().bold())
+            }
+            _ => println!("{}{}", "plan: ".bold().green(), step),
+        }
+    }
+}
+
+fn get_cancel_token() -> CancellationToken {
+    let cancel_token = CancellationToken::new();
+    let handler_token = cancel_token.clone();
+    ctrlc::set_handler(move || {
+        println!("cancelling the build on control-c...");
+        handler_token.cancel();
+    })
+    .expect("failed to set the control-c handler!");
+
+    cancel_token
+}
+
+fn build(base_dir: PathBuf, hints: Option<PathBuf>, callbacks: Callbacks<'static>) {
     if !base_dir.is_dir() {
         panic!("{} is not a directory! please enter the path to a directory with an `build.yaml` in the root of it.", base_dir.display());
     }
 
-    let mut builder = get_builder(base_dir.clone(), callbacks);
+    let cancel_token = get_cancel_token();
+    let mut builder = get_builder(base_dir.clone(), callbacks).with_cancel_token(cancel_token);
+
+    if let Some(hints_path) = hints {
+        match HintRules::from_file(hints_path) {
+            Ok(rules) => builder.hint_rules.extend(rules),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+    }
 
     thread::spawn(move || match builder.build() {
         Ok(_) => (),
         Err(e) => {
+            if let ErrorCause::CompilationFailed { output_tail, .. } = &e.cause {
+                eprintln!("{}", "last lines of the build output:".bold());
+                for line in output_tail {
+                    eprintln!("{}{}", "make: ".bold().cyan(), line);
+                }
+            }
+
             eprintln!("{}", e.to_string());
             return;
         }
@@ -36,6 +176,68 @@ fn build(base_dir: PathBuf, callbacks: Callbacks<'static>) {
     .unwrap();
 }
 
+fn build_all(
+    base_dirs: Vec<PathBuf>,
+    cpus: Option<usize>,
+    concurrent: Option<usize>,
+    mut callbacks: Callbacks<'static>,
+) {
+    let mut queue =
+        BuildQueue::new()
+            .with_cancel_token(get_cancel_token())
+            .log(|name, log_type, text| {
+                use callback_types::LogType as L;
+                let name = format!("[{}] ", name).bold();
+                match log_type {
+                    L::Error => eprintln!("{}{}{}", name, "error: ".bold().red(), text),
+                    L::Warn => eprintln!("{}{}{}", name, "warn: ".bold().magenta(), text),
+                    L::BuildOutput => println!("{}{}{}", name, "make: ".bold().cyan(), text),
+                    L::Info => println!("{}{}{}", name, "info: ".bold().blue(), text),
+                }
+            });
+
+    for base_dir in base_dirs {
+        match Spec::from_file_checked(base_dir.join("build.yaml"), &mut callbacks) {
+            Ok(spec) => queue = queue.add(spec, base_dir),
+            Err(e) => {
+                eprintln!("{}: {}", base_dir.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(cpus) = cpus {
+        queue = queue.cpu_budget(cpus);
+    }
+
+    if let Some(concurrent) = concurrent {
+        queue = queue.max_concurrent_builds(concurrent);
+    }
+
+    let report = queue.run();
+    println!("{}", report);
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
+
+fn clean(base_dir: PathBuf, action: Action, callbacks: Callbacks<'static>) {
+    let mut builder = get_builder(base_dir, callbacks);
+
+    let result = match action {
+        Action::Clean { .. } => builder.clean(),
+        Action::Distclean { .. } => builder.distclean(),
+        Action::Reset { .. } => builder.reset(),
+        _ => unreachable!(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
 fn run(base_dir: PathBuf, mut callbacks: Callbacks) {
     macro_rules! error {
         ($text:expr) => {
@@ -54,14 +256,17 @@ fn run(base_dir: PathBuf, mut callbacks: Callbacks) {
             error!(msg);
         }
     };
-    let region = spec.rom.region.to_string();
-    let path = base_dir
-        .join(&spec.repo.name)
-        .join("build")
-        .join(format!("{}_pc", &region))
-        .join(format!("sm64.{}.f3dex2e", &region));
+    let repo_dir = base_dir.join(&spec.repo.name);
+    let path = match find_executable(&spec, &repo_dir) {
+        Some(p) => p,
+        None => {
+            let msg = "could not find the executable, has the spec been built?".to_owned();
+            error!(msg);
+        }
+    };
 
     let mut cmd = std::process::Command::new(path);
+    cmd.args(get_launch_args(&spec, &repo_dir));
     let mut child = cmd.spawn().unwrap_or_else(|e| {
         let msg = format!("failed to spawn the command: {}", e);
         error!(msg);
@@ -72,11 +277,382 @@ fn run(base_dir: PathBuf, mut callbacks: Callbacks) {
     });
 }
 
+fn doctor(base_dir: Option<PathBuf>, mut callbacks: Callbacks) {
+    let spec = match base_dir {
+        Some(base_dir) => {
+            match Spec::from_file_checked(base_dir.join("build.yaml"), &mut callbacks) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let report = smbuilder::toolchain::probe(spec.as_ref());
+
+    for result in &report.results {
+        let name = result.dependency.name;
+
+        if result.found {
+            let version = result
+                .version
+                .clone()
+                .unwrap_or("unknown version".to_owned());
+            println!("{}{} ({})", "found: ".bold().green(), name, version);
+        } else if result.dependency.required {
+            println!("{}{}", "missing: ".bold().red(), name);
+        } else {
+            println!("{}{} (optional)", "missing: ".bold().magenta(), name);
+        }
+    }
+
+    if report.results.iter().all(|r| r.found) {
+        return;
+    }
+
+    match report.install_command() {
+        Some(install_cmd) => {
+            println!(
+                "{}on {}, run `{}`",
+                "fix: ".bold().yellow(),
+                report.distro,
+                install_cmd
+            );
+
+            let unpackaged = report
+                .results
+                .iter()
+                .filter(|r| !r.found && r.dependency.packages.get(report.distro).is_none());
+
+            for result in unpackaged {
+                println!(
+                    "{}{} is not packaged on {}, it has to be built from source",
+                    "fix: ".bold().yellow(),
+                    result.dependency.name,
+                    report.distro
+                );
+            }
+        }
+        None => {
+            for result in report.results.iter().filter(|r| !r.found) {
+                let packages = &result.dependency.packages;
+                println!(
+                    "{}{}: {} (Debian/Ubuntu), {} (Fedora), {} (Arch Linux), {} (openSUSE)",
+                    "fix: ".bold().yellow(),
+                    result.dependency.name,
+                    packages.debian,
+                    packages.fedora,
+                    packages.arch,
+                    packages.opensuse
+                );
+            }
+        }
+    }
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
+
+fn history(base_dir: PathBuf, id: Option<String>) {
+    let records = match BuildRecord::list(&base_dir) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let id = match id {
+        Some(id) => id,
+        None => {
+            for record in records {
+                let status = if record.succeeded {
+                    "succeeded".bold().green()
+                } else {
+                    "failed".bold().red()
+                };
+
+                println!(
+                    "{} {} in {}s",
+                    record.id.bold(),
+                    status,
+                    record.duration_ms / 1000
+                );
+            }
+
+            return;
+        }
+    };
+
+    let record = match records.into_iter().find(|r| r.id == id) {
+        Some(r) => r,
+        None => {
+            eprintln!("{}no build with the id {}", "error: ".bold().red(), id);
+            std::process::exit(1);
+        }
+    };
+
+    for stage in &record.stages {
+        let status = if stage.succeeded {
+            "done: ".bold().green()
+        } else {
+            "failed: ".bold().red()
+        };
+
+        println!("{}{} ({}ms)", status, stage.stage, stage.duration_ms);
+    }
+
+    if let Some(error) = &record.error {
+        eprintln!("{}", error);
+    }
+
+    match record.read_log(&base_dir) {
+        Ok(log) => print!("{}", log),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+fn inspect_textures(base_dir: PathBuf, pack_path: Option<PathBuf>, mut callbacks: Callbacks) {
+    let spec = match Spec::from_file_checked(base_dir.join("build.yaml"), &mut callbacks) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let repo_dir = base_dir.join(&spec.repo.name);
+
+    let packs = match pack_path {
+        Some(path) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            vec![TexturePack::new(name, path.clone())]
+        }
+        None => spec.get_texture_packs().into_iter().cloned().collect(),
+    };
+
+    let mut all_valid = true;
+
+    for pack in packs {
+        let report = match pack.inspect(&spec, &repo_dir) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        all_valid &= report.is_valid();
+
+        if report.is_valid() {
+            println!("{}{}", "valid: ".bold().green(), report);
+        } else {
+            println!("{}{}", "invalid: ".bold().red(), report);
+        }
+    }
+
+    if !all_valid {
+        std::process::exit(1);
+    }
+}
+
+fn inspect_dynos_packs(base_dir: PathBuf, pack_path: Option<PathBuf>, mut callbacks: Callbacks) {
+    let spec = match Spec::from_file_checked(base_dir.join("build.yaml"), &mut callbacks) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let packs = match pack_path {
+        Some(path) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            vec![DynosPack::new(name, path.clone())]
+        }
+        None => spec.dynos_packs.unwrap_or_default(),
+    };
+
+    let mut all_valid = true;
+
+    for pack in packs {
+        let report = match pack.inspect() {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        all_valid &= report.is_valid();
+
+        if report.is_valid() {
+            println!("{}{}", "valid: ".bold().green(), report);
+        } else {
+            println!("{}{}", "invalid: ".bold().red(), report);
+        }
+    }
+
+    if !all_valid {
+        std::process::exit(1);
+    }
+}
+
+fn mods(
+    base_dir: PathBuf,
+    enable: Option<String>,
+    disable: Option<String>,
+    mut callbacks: Callbacks,
+) {
+    let spec = match Spec::from_file_checked(base_dir.join("build.yaml"), &mut callbacks) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let repo_dir = base_dir.join(&spec.repo.name);
+
+    let changes = enable
+        .map(|name| (name, true))
+        .into_iter()
+        .chain(disable.map(|name| (name, false)));
+
+    for (name, enabled) in changes {
+        if let Err(e) = set_coop_mod_enabled(&spec, &repo_dir, &name, enabled) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let installed = match list_coop_mods(&spec, &repo_dir) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for coop_mod in installed {
+        let status = if coop_mod.enabled {
+            "enabled: ".bold().green()
+        } else {
+            "disabled: ".bold().red()
+        };
+
+        let name = coop_mod.metadata.name.unwrap_or_default();
+        println!("{}{} {}", status, coop_mod.file_name.bold(), name);
+
+        if let Some(description) = coop_mod.metadata.description {
+            println!("    {}", description);
+        }
+    }
+}
+
+fn dynos_packs(
+    base_dir: PathBuf,
+    enable: Option<String>,
+    disable: Option<String>,
+    order: Option<Vec<String>>,
+    mut callbacks: Callbacks,
+) {
+    let spec = match Spec::from_file_checked(base_dir.join("build.yaml"), &mut callbacks) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let repo_dir = base_dir.join(&spec.repo.name);
+
+    let changes = enable
+        .map(|name| (name, true))
+        .into_iter()
+        .chain(disable.map(|name| (name, false)));
+
+    for (name, enabled) in changes {
+        if let Err(e) = set_dynos_pack_enabled(&spec, &repo_dir, &name, enabled) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(order) = order {
+        if let Err(e) = set_dynos_pack_order(&spec, &repo_dir, &order) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let installed = match list_dynos_packs(&spec, &repo_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for pack in installed {
+        let status = if pack.enabled {
+            "enabled: ".bold().green()
+        } else {
+            "disabled: ".bold().red()
+        };
+
+        println!("{}{}", status, pack.name);
+    }
+}
+
+fn gc_pack_store(store_dir: PathBuf) {
+    match PackStore::new(store_dir).gc() {
+        Ok(report) => println!("{}{}", "done: ".bold().green(), report),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_diagnostic_summary(diagnostics: &[Diagnostic]) {
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .collect::<Vec<&Diagnostic>>();
+    let warning_count = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .count();
+
+    if errors.is_empty() && warning_count == 0 {
+        return;
+    }
+
+    println!(
+        "{}{} error(s), {} warning(s)",
+        "summary: ".bold().yellow(),
+        errors.len(),
+        warning_count
+    );
+
+    for error in errors {
+        eprintln!("{}{}", "error: ".bold().red(), error);
+    }
+}
+
 fn main() {
     color_eyre::install().unwrap();
 
     let args = Args::parse();
 
+    let diagnostics: Arc<Mutex<Vec<Diagnostic>>> = Arc::new(Mutex::new(Vec::new()));
+    let diagnostics_cb = Arc::clone(&diagnostics);
+
     let callbacks = Callbacks::new()
         .log(|log_type, text| {
             use callback_types::LogType as L;
@@ -97,14 +673,91 @@ fn main() {
                 (bytes_transferred as f64 / 1024_f64).floor(),
             )
         })
+        .compile_progress({
+            let mut last_percent = None;
+            move |done, total| {
+                if total == 0 {
+                    return;
+                }
+
+                // only print whole percentages, as the
+                // progress is interleaved with make's output.
+                let percent = (done * 100) / total;
+                if last_percent != Some(percent) {
+                    last_percent = Some(percent);
+                    println!(
+                        "{}{}/{} ({}%) objects compiled",
+                        "compile: ".bold().green(),
+                        done,
+                        total,
+                        percent
+                    );
+                }
+            }
+        })
+        .pack_install_progress(|name, done, total| {
+            print!(
+                "{} {}: {}/{} files installed\r",
+                "install:".bold().green(),
+                name,
+                done,
+                total
+            );
+
+            if done == total {
+                println!();
+            }
+        })
         .new_setup_stage(|stage| {
             println!("{}{}", "stage: ".bold().green(), stage.to_string());
+        })
+        .new_clean_stage(|stage| {
+            println!("{}{}", "stage: ".bold().green(), stage);
+        })
+        .diagnostic(move |diagnostic| {
+            diagnostics_cb.lock().unwrap().push(diagnostic.clone());
         });
 
     match args.subcommand {
         Action::Build {
             spec_path: base_dir,
-        } => build(base_dir, callbacks),
+            dry_run: true,
+            ..
+        } => plan(base_dir, callbacks),
+        Action::Build {
+            spec_path: base_dir,
+            hints,
+            ..
+        } => {
+            build(base_dir, hints, callbacks);
+            print_diagnostic_summary(&diagnostics.lock().unwrap());
+        }
+        Action::BuildAll {
+            spec_paths,
+            cpus,
+            concurrent,
+        } => build_all(spec_paths, cpus, concurrent, callbacks),
         Action::Run { spec_path } => run(spec_path, callbacks),
+        Action::Doctor { spec_path } => doctor(spec_path, callbacks),
+        Action::History { spec_path, id } => history(spec_path, id),
+        Action::InspectTextures { spec_path, pack } => inspect_textures(spec_path, pack, callbacks),
+        Action::InspectDynosPacks { spec_path, pack } => {
+            inspect_dynos_packs(spec_path, pack, callbacks)
+        }
+        Action::Mods {
+            spec_path,
+            enable,
+            disable,
+        } => mods(spec_path, enable, disable, callbacks),
+        Action::DynosPacks {
+            spec_path,
+            enable,
+            disable,
+            order,
+        } => dynos_packs(spec_path, enable, disable, order, callbacks),
+        Action::GcPackStore { store_dir } => gc_pack_store(store_dir),
+        Action::Clean { ref spec_path }
+        | Action::Distclean { ref spec_path }
+        | Action::Reset { ref spec_path } => clean(spec_path.clone(), args.subcommand, callbacks),
     };
 }
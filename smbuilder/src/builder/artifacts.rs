@@ -0,0 +1,278 @@
+use super::types::BuilderResult;
+
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, Spec};
+
+use duct::cmd;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::prelude::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Get the directory that a build
+/// of the spec ends up in, which is
+/// `{repo_dir}/build/{region}_pc`.
+pub fn get_build_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+    repo_dir
+        .as_ref()
+        .join("build")
+        .join(format!("{}_pc", spec.rom.region.to_string()))
+}
+
+/// Get the directory that the files of a
+/// Windows build are gathered in, which is
+/// `{base_dir}/dist`.
+pub fn get_dist_dir<P: AsRef<Path>>(base_dir: P) -> PathBuf {
+    base_dir.as_ref().join("dist")
+}
+
+/// Check if a file in the build directory
+/// looks like the game's executable.
+fn is_executable_candidate(path: &Path) -> bool {
+    let file_name = match path.file_name() {
+        Some(n) => n.to_string_lossy(),
+        None => return false,
+    };
+
+    if !file_name.starts_with("sm64") {
+        return false;
+    }
+
+    if file_name.ends_with(".exe") {
+        return true;
+    }
+
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+/// Find the executable produced by a build
+/// of the spec.
+///
+/// If the repo sets an `executable`, it is used
+/// as-is. Otherwise, the build directory is scanned
+/// for an executable named `sm64*` (such as
+/// `sm64.us.f3dex2e` or `sm64.us.exe`), preferring
+/// one for the spec's region, then the newest one.
+///
+/// Returns `None` if the spec hasn't been built.
+pub fn find_executable<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> Option<PathBuf> {
+    let build_dir = get_build_dir(spec, repo_dir);
+
+    if let Some(executable) = &spec.repo.executable {
+        let executable_path = build_dir.join(executable);
+        return executable_path.is_file().then_some(executable_path);
+    }
+
+    let region_prefix = format!("sm64.{}.", spec.rom.region.to_string());
+
+    let mut candidates = fs::read_dir(&build_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_executable_candidate(path))
+        .collect::<Vec<PathBuf>>();
+
+    candidates.sort_by_key(|path| {
+        let matches_region = path
+            .file_name()
+            .map(|n| n.to_string_lossy().starts_with(&region_prefix))
+            .unwrap_or(false);
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        (matches_region, modified)
+    });
+
+    candidates.pop()
+}
+
+/// Get the arguments that a build of the
+/// spec is launched with.
+///
+/// The ports read their config
+/// (`sm64config.txt`) and saves from the
+/// user's data directory, unless they are
+/// given a `--savepath`, so the build
+/// directory is passed as one. That way,
+/// each instance keeps its own, and the
+/// config that smbuilder writes is read.
+pub fn get_launch_args<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> Vec<OsString> {
+    vec![
+        OsString::from("--savepath"),
+        get_build_dir(spec, repo_dir).into_os_string(),
+    ]
+}
+
+/// Copy the executable of a Windows build,
+/// along with the DLLs that it needs from
+/// the MinGW toolchain, into the dist
+/// directory, which is emptied first.
+///
+/// The build directory belongs to make,
+/// so nothing is added to it.
+///
+/// DLLs that aren't in the toolchain are
+/// taken to be a part of Windows.
+///
+/// Returns the paths of the copied DLLs,
+/// which is empty for native builds.
+pub fn collect_windows_dlls<P: AsRef<Path>, Q: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+    dist_dir: Q,
+) -> BuilderResult<Vec<PathBuf>> {
+    let triple = match spec.get_target().get_triple() {
+        Some(t) => t,
+        None => return Ok(Vec::new()),
+    };
+
+    let executable = match find_executable(spec, &repo_dir) {
+        Some(e) => e,
+        None => {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                "could not find the executable in the build directory",
+            );
+            return Err(err!(c_fs!(inner_err), "whilst collecting the DLLs"));
+        }
+    };
+
+    let dist_dir = dist_dir.as_ref();
+
+    // DLLs of the last build may
+    // not be needed anymore.
+    if dist_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(dist_dir) {
+            let msg = format!("failed to remove {}", dist_dir.display());
+            return Err(err!(c_fs!(e, msg), "whilst collecting the DLLs"));
+        }
+    }
+
+    if let Err(e) = fs::create_dir_all(dist_dir) {
+        let msg = format!("failed to create {}", dist_dir.display());
+        return Err(err!(c_fs!(e, msg), "whilst collecting the DLLs"));
+    }
+
+    copy_to_dir(&executable, dist_dir)?;
+
+    let dll_dirs = get_mingw_dll_dirs(triple);
+    let objdump = format!("{}-objdump", triple);
+
+    let mut binaries = vec![executable];
+    let mut seen_dlls: Vec<String> = Vec::new();
+    let mut copied_dlls: Vec<PathBuf> = Vec::new();
+
+    // DLLs can need other DLLs too.
+    while let Some(binary) = binaries.pop() {
+        for dll_name in get_imported_dlls(&objdump, &binary)? {
+            let dll_key = dll_name.to_lowercase();
+            if seen_dlls.contains(&dll_key) {
+                continue;
+            }
+            seen_dlls.push(dll_key);
+
+            let source = match find_dll(&dll_dirs, &dll_name) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            copied_dlls.push(copy_to_dir(&source, dist_dir)?);
+            binaries.push(source);
+        }
+    }
+
+    Ok(copied_dlls)
+}
+
+/// Copy a file into a directory,
+/// and return the path of the copy.
+fn copy_to_dir(source: &Path, dir: &Path) -> BuilderResult<PathBuf> {
+    let target = dir.join(source.file_name().unwrap_or_default());
+
+    match fs::copy(source, &target) {
+        Ok(_) => Ok(target),
+        Err(e) => {
+            let msg = format!(
+                "failed to copy {} to {}",
+                source.display(),
+                target.display()
+            );
+            Err(err!(c_fs!(e, msg), "whilst collecting the DLLs"))
+        }
+    }
+}
+
+/// Get the directories that a MinGW
+/// toolchain keeps its DLLs in.
+fn get_mingw_dll_dirs(triple: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from(format!("/usr/{}/bin", triple)),
+        PathBuf::from(format!("/usr/{}/lib", triple)),
+        PathBuf::from(format!("/usr/{}/sys-root/mingw/bin", triple)),
+        PathBuf::from(format!("/usr/local/{}/bin", triple)),
+    ];
+
+    // the runtime of the compiler (libgcc,
+    // libstdc++) is kept with each version
+    // of it.
+    if let Ok(entries) = fs::read_dir(format!("/usr/lib/gcc/{}", triple)) {
+        dirs.extend(entries.flatten().map(|entry| entry.path()));
+    }
+
+    dirs
+}
+
+/// Find a DLL in a list of directories.
+///
+/// Windows doesn't care about the case of
+/// file names, so neither does this.
+fn find_dll(dirs: &[PathBuf], dll_name: &str) -> Option<PathBuf> {
+    dirs.iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().eq_ignore_ascii_case(dll_name))
+                    .unwrap_or(false)
+        })
+}
+
+/// Get the DLLs that a Windows binary
+/// imports, with `objdump`.
+fn get_imported_dlls(objdump: &str, binary: &Path) -> BuilderResult<Vec<String>> {
+    let output = match cmd!(objdump, "-p", binary)
+        .stdout_capture()
+        .stderr_null()
+        .run()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            return Err(err!(
+                c_spawn_cmd!(
+                    objdump.to_owned(),
+                    format!("failed to read the imports of {}", binary.display()),
+                    e
+                ),
+                "whilst collecting the DLLs"
+            ))
+        }
+    };
+
+    let dlls = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("DLL Name:"))
+        .map(|name| name.trim().to_owned())
+        .collect();
+
+    Ok(dlls)
+}
@@ -1,29 +1,98 @@
-use super::get_needed_setup_tasks;
-use super::types::BuilderResult;
+use super::artifacts::{collect_windows_dlls, find_executable, get_build_dir, get_dist_dir};
+use super::cancel::CancellationToken;
+use super::ccache::CcacheStats;
+use super::fingerprint::BuildFingerprint;
+use super::history::{finish_build, start_build, write_log_line, StageTiming};
+use super::state::{get_postbuild_input_hash, get_setup_input_hash, BuildState};
 use super::types::{
-    PostBuildStage::*,
+    BuilderResult, CompileOutput, PlanStep, BUILD_STOP_TIMEOUT, COMPILE_OUTPUT_TAIL_LEN,
+};
+use super::types::{
+    CleanStage,
+    PostBuildStage::{self, *},
     SetupStage::{self, *},
 };
+use super::{
+    checkout_pinned_revision, get_compiled_source, get_needed_setup_tasks, get_scripts_dir,
+    get_untracked_files, has_local_changes, is_compile_line, is_finished_clone, is_source_file,
+};
 
 use crate::callback_types::LogType::{self, *};
 use crate::callbacks::run_callback;
+use crate::diagnostics::hints::{Hint, HintRules};
+use crate::diagnostics::{get_failed_make_target, DiagnosticParser};
 use crate::error::ErrorCause;
+use crate::packs::archive::ArchiveKind;
+use crate::packs::coop::install_coop_mods;
+use crate::packs::dynos::{install_dynos_packs, uninstall_dynos_packs};
+use crate::packs::language::set_default_language;
+use crate::packs::layered::{install_layered_packs, uninstall_layered_packs, LayeredPack};
+use crate::packs::model::{install_model_packs, uninstall_model_packs};
 use crate::prelude::error_macros::*;
-use crate::prelude::{err, Callbacks, Error, Spec};
+use crate::prelude::{
+    err, Callbacks, CoopMod, DynosPack, Error, LanguagePack, PackManifest, SoundPack, Spec, Target,
+    TexturePack,
+};
 use crate::util;
 
-use duct::cmd;
+use duct::{cmd, ReaderHandle};
 use git2::build::RepoBuilder;
-use git2::{FetchOptions, RemoteCallbacks};
+use git2::{FetchOptions, RemoteCallbacks, Repository};
 use n64romconvert::{byte_swap, endian_swap, RomType};
-use std::io::{BufRead, BufReader};
-use std::sync::Arc;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter};
+use std::os::unix::process::CommandExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use std::{
     fs,
     io::Write,
     path::{Path, PathBuf},
 };
 
+/// The post-build stages, in
+/// the order that they run.
+const POSTBUILD_STAGES: [PostBuildStage; 7] = [
+    PostBuildStage::TexturePack,
+    PostBuildStage::SoundPacks,
+    PostBuildStage::LanguagePacks,
+    PostBuildStage::ModelPacks,
+    PostBuildStage::CoopMods,
+    PostBuildStage::DynOSPacks,
+    PostBuildStage::PostBuildScripts,
+];
+
+/// What compiling the spec takes,
+/// as decided by `get_build_steps`.
+enum CompileStep {
+    /// The executable at the
+    /// path is up to date.
+    UpToDate(PathBuf),
+    /// make picks up where it left off.
+    Compile,
+    /// The make flags have changed,
+    /// so it starts with `make clean`.
+    CleanAndCompile,
+    /// The ROM has changed, so it
+    /// starts with `make distclean`.
+    DistCleanAndCompile,
+}
+
+/// A step of a build, as
+/// decided by `get_build_steps`.
+enum BuildStep {
+    /// A setup stage that is needed.
+    Setup(SetupStage),
+    /// Compiling the spec.
+    Compile(CompileStep),
+    /// A post-build stage, and
+    /// whether it is already done.
+    PostBuild(PostBuildStage, bool),
+}
+
 /// The main builder class which takes care of building
 /// a spec.
 ///
@@ -58,6 +127,27 @@ pub struct Builder<'a> {
 
     /// The logger.
     pub callbacks: Callbacks<'a>,
+
+    /// The rules used to diagnose
+    /// a failed build.
+    pub hint_rules: HintRules,
+
+    /// The token that cancels
+    /// the build.
+    pub cancel_token: CancellationToken,
+
+    /// The progress of the build,
+    /// as of the last stage.
+    pub state: BuildState,
+
+    /// The log of the build that is being
+    /// saved to the history, if one is
+    /// running.
+    history_log: Arc<Mutex<Option<LineWriter<File>>>>,
+
+    /// How long each stage took since the
+    /// last build was saved to the history.
+    stage_timings: Vec<StageTiming>,
 }
 
 impl<'a> Builder<'a> {
@@ -77,43 +167,119 @@ impl<'a> Builder<'a> {
     pub fn new<P: Into<PathBuf>>(
         spec: Spec,
         base_dir: P,
-        callbacks: Callbacks,
+        mut callbacks: Callbacks,
     ) -> Result<Builder, Error> {
+        let base_dir = base_dir.into();
+        let state = BuildState::load(&base_dir).unwrap_or_default();
+
+        // write the log to the build history
+        // as it comes, then pass it on.
+        let history_log: Arc<Mutex<Option<LineWriter<File>>>> = Arc::new(Mutex::new(None));
+        let mut inner_log_cb = callbacks.log_cb.take();
+        callbacks = callbacks.log({
+            let history_log = Arc::clone(&history_log);
+            move |log_type, text| {
+                if let Some(log) = history_log.lock().unwrap().as_mut() {
+                    write_log_line(log, log_type, text);
+                }
+                run_callback!(inner_log_cb, log_type, text);
+            }
+        });
+
         let result = Builder {
             spec,
-            base_dir: base_dir.into(),
+            base_dir,
             callbacks,
+            hint_rules: HintRules::builtin(),
+            cancel_token: CancellationToken::new(),
+            state,
+            history_log,
+            stage_timings: Vec::new(),
         };
 
         Ok(result)
     }
 
+    /// Sets the token that cancels the build.
+    ///
+    /// Keep a clone of the token around, and
+    /// call `cancel` on it to stop the build.
+    pub fn with_cancel_token(mut self, cancel_token: CancellationToken) -> Self {
+        self.cancel_token = cancel_token;
+        self
+    }
+
+    /// Returns an `ErrorCause::Cancelled`
+    /// error if the build was cancelled.
+    fn check_cancelled(&self) -> BuilderResult<()> {
+        if self.cancel_token.is_cancelled() {
+            return Err(err!(c_cancelled!()));
+        }
+
+        Ok(())
+    }
+
+    /// Run a stage, and keep track
+    /// of how long it took.
+    fn time_stage<T, F>(&mut self, stage: String, f: F) -> BuilderResult<T>
+    where
+        F: FnOnce(&mut Self) -> BuilderResult<T>,
+    {
+        let start = Instant::now();
+        let result = f(self);
+
+        self.stage_timings.push(StageTiming {
+            stage,
+            duration_ms: start.elapsed().as_millis() as u64,
+            succeeded: result.is_ok(),
+        });
+
+        result
+    }
+
+    fn save_state(&self) -> BuilderResult<()> {
+        self.state.save(&self.base_dir)
+    }
+
     fn clone_repo(&mut self) -> BuilderResult<PathBuf> {
         run_callback!(self.callbacks.new_setup_stage_cb, CloneRepo);
 
         let repo_name = &self.spec.repo.name;
-        let repo_dir = Arc::new(self.base_dir.join(repo_name));
+        let repo_dir = self.base_dir.join(repo_name);
+
+        // a finished clone is moved to the new
+        // url, branch or revision in place.
+        if let Ok(repo) = Repository::open(&repo_dir) {
+            if is_finished_clone(&repo) {
+                self.update_repo(&repo)?;
+                return Ok(repo_dir);
+            }
+        }
 
-        run_callback!(self.callbacks.log_cb, Info, "cloning the repository");
+        // git refuses to clone into a directory
+        // that isn't empty.
+        if repo_dir.exists() {
+            // only a clone that was cut short is
+            // removed, anything else may be someone's.
+            if Repository::open(&repo_dir).is_err() {
+                let inner_err = io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists, but is not a repository", repo_dir.display()),
+                );
+                return Err(err!(c_fs!(inner_err), "whilst cloning the repository"));
+            }
 
-        // set up the ctrlc handler
-        let repo_dir_thread = Arc::clone(&repo_dir);
-        ctrlc::set_handler(move || {
-            let repo_dir = (*(repo_dir_thread.clone())).clone();
-            println!("exiting on control-c...");
+            run_callback!(self.callbacks.log_cb, Warn, "removing an unfinished clone");
 
-            if !repo_dir.exists() {
-                std::process::exit(0);
+            if let Err(e) = fs::remove_dir_all(&repo_dir) {
+                let msg = format!("failed to remove {}", repo_dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst cloning the repository"));
             }
+        }
 
-            fs::remove_dir_all(&repo_dir).unwrap_or_else(|e| {
-                panic!("failed to remove the dir at {}: {}", &repo_dir.display(), e)
-            });
-
-            std::process::exit(0);
-        })
-        .expect("failed to set the control-c handler!");
+        run_callback!(self.callbacks.log_cb, Info, "cloning the repository");
 
+        let cancel_token = self.cancel_token.clone();
         let mut remote_callbacks = RemoteCallbacks::new();
         remote_callbacks.transfer_progress(|progress| {
             run_callback!(
@@ -123,7 +289,8 @@ impl<'a> Builder<'a> {
                 progress.received_bytes(),
             );
 
-            true
+            // returning false aborts the transfer
+            !cancel_token.is_cancelled()
         });
 
         let mut fetch_options = FetchOptions::new();
@@ -136,19 +303,122 @@ impl<'a> Builder<'a> {
             .fetch_options(fetch_options)
             .clone(&self.spec.repo.url, &repo_dir);
 
-        match repo_clone_result {
-            Ok(_) => (),
+        let repo = match repo_clone_result {
+            Ok(r) => r,
             Err(e) => {
+                // a half-finished clone would be
+                // mistaken for a finished one.
+                if repo_dir.exists() {
+                    if let Err(e) = fs::remove_dir_all(&repo_dir) {
+                        let msg = format!("failed to remove {}", repo_dir.display());
+                        return Err(err!(c_fs!(e, msg), "whilst cleaning up a failed clone"));
+                    }
+                }
+
+                self.check_cancelled()?;
+
                 let msg = e.message().to_string();
                 let err = err!(
-                    c_repo_clone!(self.spec.repo.url.clone(), (*repo_dir).clone(), e),
+                    c_repo_clone!(self.spec.repo.url.clone(), repo_dir.clone(), e),
                     format!("failed to clone the repository: {}", msg)
                 );
                 return Err(err);
             }
+        };
+
+        if self.spec.repo.rev.is_some() {
+            checkout_pinned_revision(&self.spec, &repo)?;
+        }
+
+        Ok(repo_dir)
+    }
+
+    /// Fetch the spec's branch into a finished
+    /// clone, from the spec's url, and check out
+    /// the pinned revision (or the tip of the
+    /// branch).
+    ///
+    /// Refuses to touch a repository with local
+    /// changes, those have to be reset first.
+    fn update_repo(&mut self, repo: &Repository) -> BuilderResult<()> {
+        let url = &self.spec.repo.url;
+        let branch = &self.spec.repo.branch;
+
+        if has_local_changes(repo)? {
+            let inner_err = io::Error::other(
+                "the repository has local changes, reset it first to switch it to the spec's url, branch or revision",
+            );
+            return Err(err!(c_fs!(inner_err), "whilst updating the repository"));
+        }
+
+        run_callback!(self.callbacks.log_cb, Info, "updating the repository");
+
+        if let Err(e) = repo.remote_set_url("origin", url) {
+            return Err(err!(c_other!(e), "failed to set the url of the repository"));
+        }
+
+        let mut remote = match repo.find_remote("origin") {
+            Ok(r) => r,
+            Err(e) => return Err(err!(c_other!(e), "failed to find the origin remote")),
+        };
+
+        let fetch_result = {
+            let cancel_token = self.cancel_token.clone();
+            let mut remote_callbacks = RemoteCallbacks::new();
+            remote_callbacks.transfer_progress(|progress| {
+                run_callback!(
+                    self.callbacks.repo_clone_progress_cb,
+                    progress.received_objects(),
+                    progress.total_objects(),
+                    progress.received_bytes(),
+                );
+
+                // returning false aborts the transfer
+                !cancel_token.is_cancelled()
+            });
+
+            let mut fetch_options = FetchOptions::new();
+            fetch_options
+                .remote_callbacks(remote_callbacks)
+                .follow_redirects(git2::RemoteRedirect::All);
+
+            let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch);
+            remote.fetch(&[refspec], Some(&mut fetch_options), None)
+        };
+
+        if let Err(e) = fetch_result {
+            self.check_cancelled()?;
+
+            let msg = e.message().to_string();
+            let err = err!(
+                c_repo_clone!(url.clone(), self.base_dir.join(&self.spec.repo.name), e),
+                format!("failed to fetch the repository: {}", msg)
+            );
+            return Err(err);
+        }
+
+        // the branch that is checked out
+        // follows the spec's branch.
+        let head_branch = repo
+            .head()
+            .ok()
+            .and_then(|h| h.shorthand().map(str::to_owned));
+        if head_branch.as_ref() != Some(branch) {
+            let tip = repo
+                .revparse_single(&format!("origin/{}", branch))
+                .and_then(|o| o.peel_to_commit())
+                .and_then(|commit| repo.branch(branch, &commit, true));
+
+            let result = tip.and_then(|_| repo.set_head(&format!("refs/heads/{}", branch)));
+            if let Err(e) = result {
+                return Err(err!(
+                    c_other!(e),
+                    format!("failed to switch the repository to {}", branch)
+                ));
+            }
         }
 
-        Ok((*repo_dir).clone())
+        checkout_pinned_revision(&self.spec, repo)
     }
 
     fn copy_rom<P: AsRef<Path>>(&mut self, repo_dir: P) -> BuilderResult<()> {
@@ -221,10 +491,10 @@ impl<'a> Builder<'a> {
         Ok(())
     }
 
-    fn create_scripts_dir<P: AsRef<Path>>(&mut self, base_dir: P) -> BuilderResult<PathBuf> {
+    fn create_scripts_dir(&mut self) -> BuilderResult<PathBuf> {
         run_callback!(self.callbacks.new_setup_stage_cb, CreateScriptsDir);
 
-        let scripts_dir = base_dir.as_ref().join("scripts");
+        let scripts_dir = get_scripts_dir(&self.base_dir);
 
         if !scripts_dir.exists() {
             match fs::create_dir(&scripts_dir) {
@@ -242,99 +512,770 @@ impl<'a> Builder<'a> {
         Ok(scripts_dir)
     }
 
-    fn write_scripts<P: AsRef<Path>>(&mut self, scripts_dir: P) {
+    fn write_scripts(&mut self) -> BuilderResult<()> {
         run_callback!(self.callbacks.new_setup_stage_cb, WritePostBuildScripts);
 
+        let scripts_dir = get_scripts_dir(&self.base_dir);
+
         if let Some(scripts) = &mut self.spec.scripts {
             for script in scripts {
-                let script_path = script.save(&scripts_dir).unwrap(); // BUG: unwrap
+                let script_path = script.save(&scripts_dir)?;
 
                 util::make_file_executable(&script_path);
             }
         }
+
+        Ok(())
+    }
+
+    /// Clone the repository if it is needed,
+    /// without running the other setup stages,
+    /// which `build` runs.
+    ///
+    /// Cloning is bound by the network rather
+    /// than the CPU, so it can be done ahead
+    /// of the build.
+    pub(crate) fn clone_if_needed(&mut self) -> BuilderResult<()> {
+        let needed_stages =
+            get_needed_setup_tasks(&self.spec, &self.base_dir, &self.state, &mut self.callbacks)?;
+
+        if !needed_stages.contains(&CloneRepo) {
+            return Ok(());
+        }
+
+        self.adopt_setup_stages(&needed_stages)?;
+        self.run_setup_stage(CloneRepo)
+    }
+
+    /// Take the setup stages that are not needed
+    /// as done, if smbuilder hasn't kept track of
+    /// them yet.
+    ///
+    /// They were done before it did.
+    fn adopt_setup_stages(&mut self, needed_stages: &[SetupStage]) -> BuilderResult<()> {
+        if !self.state.is_empty() || self.state.in_progress.is_some() {
+            return Ok(());
+        }
+
+        let stages = [
+            CloneRepo,
+            CopyRom,
+            CreateBuildScript,
+            CreateScriptsDir,
+            WritePostBuildScripts,
+        ];
+
+        for stage in stages {
+            if !needed_stages.contains(&stage) {
+                let input_hash = get_setup_input_hash(&self.spec, stage)?;
+                self.state.complete_setup(stage, input_hash);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_setup_stage(&mut self, stage: SetupStage) -> BuilderResult<()> {
+        self.check_cancelled()?;
+
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+        let input_hash = get_setup_input_hash(&self.spec, stage)?;
+
+        self.state.in_progress = Some(stage);
+        self.save_state()?;
+
+        self.time_stage(stage.to_string(), |builder| match stage {
+            CloneRepo => builder.clone_repo().map(|_| ()),
+            CopyRom => builder.copy_rom(&repo_dir),
+            CreateBuildScript => builder.create_build_script(&repo_dir),
+            CreateScriptsDir => builder.create_scripts_dir().map(|_| ()),
+            WritePostBuildScripts => builder.write_scripts(),
+        })?;
+
+        self.state.complete_setup(stage, input_hash);
+        self.save_state()
+    }
+
+    /// Estimate the amount of object files that
+    /// the build will compile, from the source
+    /// files whose object files are missing or
+    /// older than them.
+    ///
+    /// make isn't asked, as even a dry run of
+    /// it runs the `$(shell ...)` calls in the
+    /// makefile, which extract the assets.
+    ///
+    /// Returns `0` if the repo can't be read.
+    fn estimate_compile_jobs(&mut self) -> usize {
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+        let build_dir = get_build_dir(&self.spec, &repo_dir);
+
+        let entries = match fs::read_dir(&repo_dir) {
+            Ok(e) => e,
+            Err(e) => {
+                run_callback!(
+                    self.callbacks.log_cb,
+                    Warn,
+                    &format!("failed to estimate the compile progress: {}", e)
+                );
+                return 0;
+            }
+        };
+
+        // the host tools are not
+        // a part of the game.
+        let source_dirs = entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name())
+            .filter(|name| !matches!(name.to_str(), Some(".git" | "build" | "tools")));
+
+        let mut sources: Vec<PathBuf> = Vec::new();
+        for dir_name in source_dirs {
+            let files = util::get_files_recursive(repo_dir.join(&dir_name)).unwrap_or_default();
+            sources.extend(
+                files
+                    .into_iter()
+                    .map(|file| Path::new(&dir_name).join(file)),
+            );
+        }
+
+        sources
+            .iter()
+            .filter(|source| is_source_file(source))
+            .filter(|source| {
+                let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+                let object_modified = modified(&build_dir.join(source).with_extension("o"));
+
+                match (object_modified, modified(&repo_dir.join(source))) {
+                    (Some(object), Some(source)) => object < source,
+                    _ => true,
+                }
+            })
+            .count()
     }
 
-    fn setup_build(&mut self) -> BuilderResult<()> {
-        use SetupStage::*;
+    fn run_make_target(&mut self, target: &str, context: &str) -> BuilderResult<()> {
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+        let make_cmd = format!("{} {}", self.spec.get_make_cmd(&repo_dir), target);
+
+        let output = match cmd!("sh", "-c", &make_cmd)
+            .stderr_to_stdout()
+            .stdout_capture()
+            .unchecked()
+            .run()
+        {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(err!(
+                    c_spawn_cmd!(make_cmd, format!("failed to run make {}", target), e),
+                    context
+                ))
+            }
+        };
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            run_callback!(self.callbacks.log_cb, BuildOutput, line);
+        }
+
+        if !output.status.success() {
+            return Err(err!(
+                c_comp_failed!(output.status.code(), Some(target.to_owned()), Vec::new()),
+                context
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Remove the compiled files of the
+    /// spec, with `make clean`.
+    ///
+    /// The extracted assets are kept, so it
+    /// is not enough when the ROM changes;
+    /// use `distclean` for that.
+    pub fn clean(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_clean_stage_cb, CleanStage::Clean);
+
+        self.run_make_target("clean", "whilst cleaning the build")?;
+
+        // the packs are removed along
+        // with the build directory.
+        self.state.reset_postbuild();
+        self.save_state()
+    }
+
+    /// Remove the compiled files and the assets
+    /// extracted from the ROM, with `make distclean`.
+    ///
+    /// The next build will start from scratch,
+    /// but the repository is left untouched.
+    pub fn distclean(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_clean_stage_cb, CleanStage::DistClean);
+
+        self.run_make_target("distclean", "whilst cleaning the build")?;
+
+        // ports may not remove the whole build
+        // directory, which is where the packs live.
+        let build_dir = self.base_dir.join(&self.spec.repo.name).join("build");
+        if build_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&build_dir) {
+                let msg = format!("failed to remove {}", build_dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst cleaning the build"));
+            }
+        }
+
+        self.state.reset_postbuild();
+        self.save_state()?;
+
+        self.remove_fingerprint()
+    }
 
-        let needed_targets =
-            get_needed_setup_tasks(&self.spec, &self.base_dir, &mut self.callbacks);
+    /// Reset the repository to the pinned revision
+    /// (or the tip of the branch), dropping any
+    /// changes made by patches, and remove the build
+    /// directory along with the installed packs.
+    ///
+    /// The ROM and the extracted assets are kept.
+    pub fn reset(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_clean_stage_cb, CleanStage::Reset);
 
         let repo_dir = self.base_dir.join(&self.spec.repo.name);
-        let scripts_dir = repo_dir.join("scripts");
 
-        for target in needed_targets {
-            match target {
-                CloneRepo => {
-                    let _ = self.clone_repo();
+        let repo = match Repository::open(&repo_dir) {
+            Ok(r) => r,
+            Err(e) => {
+                return Err(err!(
+                    c_other!(e),
+                    format!("failed to open the repository at {}", repo_dir.display())
+                ))
+            }
+        };
+
+        checkout_pinned_revision(&self.spec, &repo)?;
+
+        // files that were added by patches
+        // are not tracked, so they have to
+        // be removed by hand.
+        let mut parent_dirs = Vec::new();
+        for path in get_untracked_files(&repo)? {
+            let path = repo_dir.join(path);
+            run_callback!(
+                self.callbacks.log_cb,
+                Info,
+                &format!("removing {}", path.display())
+            );
+
+            if let Err(e) = fs::remove_file(&path) {
+                let msg = format!("failed to remove {}", path.display());
+                return Err(err!(c_fs!(e, msg), "whilst resetting the repository"));
+            }
+
+            if let Some(parent) = path.parent() {
+                parent_dirs.push(parent.to_path_buf());
+            }
+        }
+
+        // git doesn't track directories, so the
+        // ones that are left empty go too, up
+        // to the first one that isn't empty.
+        for parent in parent_dirs {
+            for dir in parent.ancestors().take_while(|d| *d != repo_dir) {
+                if fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+
+        let build_dir = repo_dir.join("build");
+        if build_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&build_dir) {
+                let msg = format!("failed to remove {}", build_dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst resetting the repository"));
+            }
+        }
+
+        self.state.reset_postbuild();
+        self.save_state()?;
+
+        self.remove_fingerprint()
+    }
+
+    fn remove_fingerprint(&mut self) -> BuilderResult<()> {
+        let fingerprint_path = BuildFingerprint::get_path(&self.base_dir);
+
+        if !fingerprint_path.exists() {
+            return Ok(());
+        }
+
+        match fs::remove_file(&fingerprint_path) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let msg = format!("failed to remove {}", fingerprint_path.display());
+                Err(err!(c_fs!(e, msg), "whilst removing the build fingerprint"))
+            }
+        }
+    }
+
+    fn compile(&mut self) -> BuilderResult<CompileOutput> {
+        // the estimate has to look at every
+        // source file, so only make it if
+        // someone is listening.
+        self.check_cancelled()?;
+
+        let mut compile_jobs_total = if self.callbacks.compile_progress_cb.is_some() {
+            run_callback!(
+                self.callbacks.log_cb,
+                Info,
+                "estimating the compile progress"
+            );
+            self.estimate_compile_jobs()
+        } else {
+            0
+        };
+        let mut compile_jobs_done: usize = 0;
+        let mut compiled_sources: HashSet<String> = HashSet::new();
+
+        let ccache_dir = self
+            .spec
+            .compiler
+            .as_ref()
+            .filter(|compiler| compiler.ccache)
+            .map(|compiler| compiler.ccache_dir.clone());
+        let cache_stats_before = match &ccache_dir {
+            Some(dir) => CcacheStats::read(dir.as_deref()),
+            None => None,
+        };
+
+        let build_script_path = self.base_dir.join("build.sh");
+        let build_script_path = match build_script_path.canonicalize() {
+            Ok(p) => p,
+            Err(e) => {
+                let msg = format!(
+                    "failed to get the absolute path of the build script at {}",
+                    build_script_path.display()
+                );
+                return Err(err!(c_fs!(e, msg), "whilst compiling the spec"));
+            }
+        };
+
+        let build_cmd = cmd!(&build_script_path)
+            .stderr_to_stdout()
+            .unchecked()
+            // make and everything that it runs get a
+            // process group of their own, so that they
+            // can be stopped together.
+            .before_spawn(|cmd| {
+                cmd.process_group(0);
+                Ok(())
+            });
+        let handle = match build_cmd.reader() {
+            Ok(h) => h,
+            Err(e) => {
+                return Err(err!(
+                    c_spawn_cmd!(
+                        build_script_path.to_string_lossy().to_string(),
+                        "failed to start the build",
+                        e
+                    ),
+                    "whilst compiling the spec"
+                ))
+            }
+        };
+
+        let mut output_tail: VecDeque<String> = VecDeque::with_capacity(COMPILE_OUTPUT_TAIL_LEN);
+        let mut failed_target: Option<String> = None;
+        let mut diagnostic_parser = DiagnosticParser::new();
+        let mut hints: Vec<Hint> = Vec::new();
+
+        let cancel_token = self.cancel_token.clone();
+        let build_finished = AtomicBool::new(false);
+        let mut read_error: Option<std::io::Error> = None;
+
+        let was_killed = thread::scope(|scope| {
+            // reading the output blocks until make
+            // exits, so make has to be stopped from
+            // another thread to cancel the build.
+            let watcher = scope.spawn(|| {
+                while !build_finished.load(Ordering::SeqCst) {
+                    if cancel_token.is_cancelled() {
+                        return stop_build(&handle, &build_finished);
+                    }
+
+                    thread::sleep(Duration::from_millis(100));
+                }
+
+                false
+            });
+
+            for line in BufReader::new(&handle).lines() {
+                let ln = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        read_error = Some(e);
+                        break;
+                    }
+                };
+
+                // only keep the first failure, the rest
+                // are make giving up on its parent targets.
+                if failed_target.is_none() {
+                    failed_target = get_failed_make_target(&ln);
+                }
+
+                run_callback!(self.callbacks.log_cb, BuildOutput, &ln);
+
+                // quiet make rules may be printed along
+                // with the compiler invocations, so each
+                // source file is only counted once.
+                let is_new_compile = is_compile_line(&ln)
+                    && compiled_sources.insert(get_compiled_source(&ln).unwrap_or(&ln).to_owned());
+
+                if is_new_compile {
+                    compile_jobs_done += 1;
+                    if compile_jobs_total != 0 {
+                        compile_jobs_total = compile_jobs_total.max(compile_jobs_done);
+                    }
+
+                    run_callback!(
+                        self.callbacks.compile_progress_cb,
+                        compile_jobs_done,
+                        compile_jobs_total
+                    );
                 }
-                CopyRom => {
-                    self.copy_rom(&repo_dir)?;
+
+                if let Some(diagnostic) = diagnostic_parser.parse_line(&ln) {
+                    run_callback!(self.callbacks.diagnostic_cb, &diagnostic);
                 }
-                CreateBuildScript => {
-                    self.create_build_script(&repo_dir)?;
+
+                if let Some(hint) = self.hint_rules.match_line(&ln) {
+                    if !hints.iter().any(|h| h.rule == hint.rule) {
+                        hints.push(hint);
+                    }
                 }
-                CreateScriptsDir => {
-                    let _ = self.create_scripts_dir(self.base_dir.clone())?;
+
+                if output_tail.len() == COMPILE_OUTPUT_TAIL_LEN {
+                    output_tail.pop_front();
                 }
-                WritePostBuildScripts => self.write_scripts(&scripts_dir),
+                output_tail.push_back(ln);
             }
+
+            build_finished.store(true, Ordering::SeqCst);
+            watcher.join().unwrap_or(false)
+        });
+
+        // make deletes the targets that it was in the
+        // middle of building when it is interrupted, so
+        // the next build picks up where this one left
+        // off. the build fingerprint is only saved on
+        // success. a killed make can't clean up after
+        // itself though, and its half-written files
+        // look up to date to the next build.
+        if was_killed {
+            run_callback!(
+                self.callbacks.log_cb,
+                Warn,
+                "make had to be killed, so it may have left half-built files behind; clean the build if the next one fails"
+            );
         }
 
-        Ok(())
-    }
+        self.check_cancelled()?;
+
+        if let Some(e) = read_error {
+            return Err(err!(
+                c_fs!(e, "failed to read the build output"),
+                "whilst compiling the spec"
+            ));
+        }
+
+        // the output may end before the build does,
+        // if make closes it early, and the reader
+        // has no blocking wait, so it is polled.
+        let status = loop {
+            match handle.try_wait() {
+                Ok(Some(output)) => break output.status,
+                Ok(None) => thread::sleep(Duration::from_millis(100)),
+                Err(e) => {
+                    return Err(err!(
+                        c_spawn_cmd!(
+                            build_script_path.to_string_lossy().to_string(),
+                            "failed to wait on the build",
+                            e
+                        ),
+                        "whilst compiling the spec"
+                    ))
+                }
+            }
+        };
 
-    fn compile(&mut self) {
-        let build_script_path = self.base_dir.join("build.sh").canonicalize().unwrap();
-        dbg!(&build_script_path);
-        let build_cmd = cmd!(build_script_path).stderr_to_stdout();
-        let output = build_cmd
-            .reader()
-            .unwrap_or_else(|e| panic!("failed to get a reader from the command: {}", e));
-        let reader = BufReader::new(output);
+        let output_tail = Vec::from(output_tail);
 
-        for line in reader.lines() {
-            let ln = match line {
-                Ok(line) => line,
-                Err(e) => panic!("something went wrong: {}", e),
-            }; // exit when there is no more output
+        if !status.success() {
+            return Err(err!(
+                c_comp_failed!(status.code(), failed_target, output_tail),
+                "whilst compiling the spec"
+            )
+            .with_hints(hints));
+        }
 
-            run_callback!(self.callbacks.log_cb, BuildOutput, &ln);
+        let cache_stats = match (&ccache_dir, cache_stats_before) {
+            (Some(dir), Some(before)) => {
+                CcacheStats::read(dir.as_deref()).map(|after| after.since(&before))
+            }
+            _ => None,
+        };
+
+        if let Some(stats) = &cache_stats {
+            run_callback!(self.callbacks.log_cb, Info, &format!("ccache: {}", stats));
         }
+
+        Ok(CompileOutput {
+            status: status.code().unwrap_or(0),
+            output_tail,
+            cache_stats,
+        })
     }
 
-    fn install_texture_pack(&mut self) -> BuilderResult<()> {
+    fn install_texture_packs(&mut self) -> BuilderResult<()> {
         run_callback!(self.callbacks.new_postbuild_stage_cb, TexturePack);
 
-        let pack = if let Some(pack) = &self.spec.texture_pack {
-            pack
-        } else {
+        let repo_dir = &self.base_dir.join(&self.spec.repo.name);
+
+        let packs = self.spec.get_texture_packs();
+        if packs.is_empty() {
+            // the spec may have had
+            // packs that were removed.
+            uninstall_layered_packs::<TexturePack, _>(&self.spec, repo_dir)?;
+            let manifest = TexturePack::get_manifest(&self.spec, repo_dir)?;
+            self.warn_kept_backups(&manifest);
             return Ok(());
+        }
+
+        let progress_cb = &mut self.callbacks.pack_install_progress_cb;
+        let manifest = install_layered_packs(
+            &self.spec,
+            repo_dir,
+            packs.iter().copied(),
+            |name, done, total| {
+                run_callback!(*progress_cb, name, done, total);
+            },
+        )?;
+
+        run_callback!(
+            self.callbacks.log_cb,
+            Info,
+            &format!(
+                "installed {} texture(s) from {} pack(s)",
+                manifest.files.len(),
+                packs.len()
+            )
+        );
+
+        self.warn_kept_backups(&manifest);
+
+        Ok(())
+    }
+
+    fn install_sound_packs(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_postbuild_stage_cb, SoundPacks);
+
+        let repo_dir = &self.base_dir.join(&self.spec.repo.name);
+
+        let packs = match &self.spec.sound_packs {
+            Some(p) if !p.is_empty() => p,
+            // the spec may have had
+            // packs that were removed.
+            _ => {
+                uninstall_layered_packs::<SoundPack, _>(&self.spec, repo_dir)?;
+                let manifest = SoundPack::get_manifest(&self.spec, repo_dir)?;
+                self.warn_kept_backups(&manifest);
+                return Ok(());
+            }
         };
 
+        let progress_cb = &mut self.callbacks.pack_install_progress_cb;
+        let manifest = install_layered_packs(&self.spec, repo_dir, packs, |name, done, total| {
+            run_callback!(*progress_cb, name, done, total);
+        })?;
+
+        run_callback!(
+            self.callbacks.log_cb,
+            Info,
+            &format!(
+                "installed {} sound file(s) from {} pack(s)",
+                manifest.files.len(),
+                packs.len()
+            )
+        );
+
+        self.warn_kept_backups(&manifest);
+
+        Ok(())
+    }
+
+    fn install_language_packs(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_postbuild_stage_cb, LanguagePacks);
+
         let repo_dir = &self.base_dir.join(&self.spec.repo.name);
 
-        pack.install(&self.spec, repo_dir)?;
+        let manifest = match &self.spec.language_packs {
+            Some(packs) if !packs.is_empty() => {
+                let progress_cb = &mut self.callbacks.pack_install_progress_cb;
+                install_layered_packs(&self.spec, repo_dir, packs, |name, done, total| {
+                    run_callback!(*progress_cb, name, done, total);
+                })?
+            }
+            _ => {
+                uninstall_layered_packs::<LanguagePack, _>(&self.spec, repo_dir)?;
+                LanguagePack::get_manifest(&self.spec, repo_dir)?
+            }
+        };
+
+        self.warn_kept_backups(&manifest);
+
+        if let Some(language) = &self.spec.default_language {
+            set_default_language(&self.spec, repo_dir, language)?;
+
+            run_callback!(
+                self.callbacks.log_cb,
+                Info,
+                &format!("the game will start in {}", language)
+            );
+        }
 
         Ok(())
     }
 
+    /// Warns about the files that removed packs
+    /// overwrote, which were kept instead of
+    /// being put back.
+    fn warn_kept_backups(&mut self, manifest: &PackManifest) {
+        for kept in &manifest.kept_backups {
+            // they may have been dealt with.
+            if !kept.backup.exists() {
+                continue;
+            }
+
+            run_callback!(
+                self.callbacks.log_cb,
+                Warn,
+                &format!(
+                    "{} was changed after its pack installed it, so the file that the pack replaced was kept at {}",
+                    kept.path.display(),
+                    kept.backup.display()
+                )
+            );
+        }
+    }
+
+    fn install_model_packs(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_postbuild_stage_cb, ModelPacks);
+
+        let repo_dir = &self.base_dir.join(&self.spec.repo.name);
+
+        let packs = match &self.spec.model_packs {
+            Some(p) if !p.is_empty() => p,
+            _ => return uninstall_model_packs(&self.spec, repo_dir),
+        };
+
+        if !self.spec.repo.supports_dynos {
+            run_callback!(
+                self.callbacks.log_cb,
+                Warn,
+                "this build does not support model packs, as they need DynOS. stopping."
+            );
+            return Ok(());
+        }
+
+        let progress_cb = &mut self.callbacks.pack_install_progress_cb;
+        install_model_packs(&self.spec, repo_dir, packs, |name, done, total| {
+            run_callback!(*progress_cb, name, done, total);
+        })
+    }
+
+    fn install_coop_mods(&mut self) -> BuilderResult<()> {
+        run_callback!(self.callbacks.new_postbuild_stage_cb, CoopMods);
+
+        let repo_dir = &self.base_dir.join(&self.spec.repo.name);
+
+        // installing no mods still keeps the
+        // ones that were disabled by hand that way.
+        let mods = self.spec.coop_mods.as_deref().unwrap_or_default();
+
+        if !mods.is_empty() && !self.spec.repo.supports_coop_mods {
+            run_callback!(
+                self.callbacks.log_cb,
+                Warn,
+                "this build does not support coop mods. stopping."
+            );
+            return Ok(());
+        }
+
+        let progress_cb = &mut self.callbacks.pack_install_progress_cb;
+        install_coop_mods(&self.spec, repo_dir, mods, |name, done, total| {
+            run_callback!(*progress_cb, name, done, total);
+        })
+    }
+
     fn install_dynos_packs(&mut self) -> BuilderResult<()> {
         run_callback!(self.callbacks.new_postbuild_stage_cb, DynOSPacks);
 
-        let packs = if let Some(packs) = &self.spec.dynos_packs {
-            packs
-        } else {
-            return Ok(());
+        let repo_dir = &self.base_dir.join(&self.spec.repo.name);
+
+        let packs = match &self.spec.dynos_packs {
+            Some(p) if !p.is_empty() => p,
+            _ => return uninstall_dynos_packs(&self.spec, repo_dir),
         };
 
-        let repo_dir = &self.base_dir.join(&self.spec.repo.name);
+        if !self.spec.repo.supports_dynos {
+            run_callback!(
+                self.callbacks.log_cb,
+                Warn,
+                "this build does not support DynOS packs. stopping."
+            );
+            return Ok(());
+        }
 
+        // they are still installed, as DynOS
+        // may load more than the report knows of.
         for pack in packs {
-            pack.install(&self.spec, repo_dir, &mut self.callbacks)?;
+            let report = match pack.inspect() {
+                Ok(r) => r,
+                Err(e) => {
+                    run_callback!(
+                        self.callbacks.log_cb,
+                        Warn,
+                        &format!("failed to inspect {}: {}", pack.name, e)
+                    );
+                    continue;
+                }
+            };
+
+            for path in &report.unknown_files {
+                run_callback!(
+                    self.callbacks.log_cb,
+                    Warn,
+                    &format!(
+                        "{} has a file that DynOS doesn't load: {}",
+                        pack.name,
+                        path.display()
+                    )
+                );
+            }
+
+            if report.is_empty() {
+                run_callback!(
+                    self.callbacks.log_cb,
+                    Warn,
+                    &format!("{} has nothing in it that DynOS loads", pack.name)
+                );
+            }
         }
 
-        Ok(())
+        let progress_cb = &mut self.callbacks.pack_install_progress_cb;
+        install_dynos_packs(&self.spec, repo_dir, packs, |name, done, total| {
+            run_callback!(*progress_cb, name, done, total);
+        })
     }
 
     fn run_postbuild_scripts(&mut self) -> BuilderResult<()> {
@@ -347,6 +1288,10 @@ impl<'a> Builder<'a> {
         };
 
         for script in scripts {
+            if self.cancel_token.is_cancelled() {
+                return Err(err!(c_cancelled!()));
+            }
+
             run_callback!(
                 self.callbacks.new_postbuild_script_cb,
                 &script.name,
@@ -376,12 +1321,378 @@ impl<'a> Builder<'a> {
         Ok(())
     }
 
-    fn post_build(&mut self) -> BuilderResult<()> {
-        self.install_texture_pack()?;
-        self.install_dynos_packs()?;
-        self.run_postbuild_scripts()?;
+    fn run_postbuild_stage(&mut self, stage: PostBuildStage) -> BuilderResult<()> {
+        self.check_cancelled()?;
 
-        Ok(())
+        let input_hash = get_postbuild_input_hash(&self.spec, stage);
+
+        self.time_stage(stage.to_string(), |builder| match stage {
+            TexturePack => builder.install_texture_packs(),
+            SoundPacks => builder.install_sound_packs(),
+            LanguagePacks => builder.install_language_packs(),
+            ModelPacks => builder.install_model_packs(),
+            CoopMods => builder.install_coop_mods(),
+            DynOSPacks => builder.install_dynos_packs(),
+            PostBuildScripts => builder.run_postbuild_scripts(),
+        })?;
+
+        // it is left to be done, so that it
+        // is once the build supports it.
+        if self.is_postbuild_skipped(stage) {
+            return Ok(());
+        }
+
+        self.state.complete_postbuild(stage, input_hash);
+        self.save_state()
+    }
+
+    /// Checks if a post-build stage has
+    /// something to install that the
+    /// build doesn't support, so it
+    /// didn't install anything.
+    fn is_postbuild_skipped(&self, stage: PostBuildStage) -> bool {
+        let (count, is_supported) = match stage {
+            ModelPacks => (
+                self.spec.model_packs.as_ref().map_or(0, Vec::len),
+                self.spec.repo.supports_dynos,
+            ),
+            DynOSPacks => (
+                self.spec.dynos_packs.as_ref().map_or(0, Vec::len),
+                self.spec.repo.supports_dynos,
+            ),
+            CoopMods => (
+                self.spec.coop_mods.as_ref().map_or(0, Vec::len),
+                self.spec.repo.supports_coop_mods,
+            ),
+            _ => return false,
+        };
+
+        count > 0 && !is_supported
+    }
+
+    /// Decide the steps that a build of the spec
+    /// takes, from the spec, the state of the
+    /// instance and what is on disk.
+    ///
+    /// `build` runs them, and `plan` lays
+    /// them out.
+    fn get_build_steps(&mut self) -> BuilderResult<Vec<BuildStep>> {
+        let needed_stages =
+            get_needed_setup_tasks(&self.spec, &self.base_dir, &self.state, &mut self.callbacks)?;
+
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+
+        let fingerprint = BuildFingerprint::new(&self.spec, &repo_dir)?;
+        let last_fingerprint = BuildFingerprint::load(&self.base_dir);
+        let executable_path = find_executable(&self.spec, &repo_dir);
+
+        // cloning may check out another commit, so
+        // the executable can only be up to date if
+        // the repository is left alone.
+        let compile_step = match (executable_path, &last_fingerprint) {
+            (Some(executable_path), Some(last))
+                if *last == fingerprint && !needed_stages.contains(&CloneRepo) =>
+            {
+                CompileStep::UpToDate(executable_path)
+            }
+            (_, Some(last)) => match fingerprint.needs_clean(last) {
+                Some(CleanStage::DistClean) => CompileStep::DistCleanAndCompile,
+                Some(_) => CompileStep::CleanAndCompile,
+                None => CompileStep::Compile,
+            },
+            (_, None) => CompileStep::Compile,
+        };
+
+        // the packs have to go on top of
+        // freshly compiled files again.
+        let compiles = !matches!(compile_step, CompileStep::UpToDate(_));
+
+        let mut steps: Vec<BuildStep> = needed_stages.into_iter().map(BuildStep::Setup).collect();
+        steps.push(BuildStep::Compile(compile_step));
+
+        for stage in POSTBUILD_STAGES {
+            let input_hash = get_postbuild_input_hash(&self.spec, stage);
+            let done = !compiles && self.state.is_postbuild_done(stage, &input_hash);
+            steps.push(BuildStep::PostBuild(stage, done));
+        }
+
+        Ok(steps)
+    }
+
+    /// Lay out what a setup stage would do.
+    fn plan_setup_stage(
+        &self,
+        target: SetupStage,
+        copy_rom_step: &PlanStep,
+        steps: &mut Vec<PlanStep>,
+    ) {
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+        let scripts_dir = get_scripts_dir(&self.base_dir);
+
+        match target {
+            CloneRepo => {
+                let finished_clone = Repository::open(&repo_dir)
+                    .map(|repo| is_finished_clone(&repo))
+                    .unwrap_or(false);
+
+                if finished_clone {
+                    steps.push(PlanStep::Fetch {
+                        url: self.spec.repo.url.clone(),
+                        branch: self.spec.repo.branch.clone(),
+                        rev: self.spec.repo.rev.clone(),
+                        repo: repo_dir.clone(),
+                    });
+                    return;
+                }
+
+                if repo_dir.exists() {
+                    steps.push(PlanStep::Remove(repo_dir.clone()));
+                }
+
+                steps.push(PlanStep::Clone {
+                    url: self.spec.repo.url.clone(),
+                    branch: self.spec.repo.branch.clone(),
+                    rev: self.spec.repo.rev.clone(),
+                    dest: repo_dir.clone(),
+                });
+            }
+            CopyRom => steps.push(copy_rom_step.clone()),
+            CreateBuildScript => steps.push(PlanStep::WriteFile {
+                path: self.base_dir.join("build.sh"),
+                contents: self.spec.to_script(&repo_dir),
+            }),
+            CreateScriptsDir => {
+                if !scripts_dir.exists() {
+                    steps.push(PlanStep::CreateDir(scripts_dir));
+                }
+            }
+            WritePostBuildScripts => {
+                for script in self.spec.scripts.iter().flatten() {
+                    let mut path = scripts_dir.join(&script.name);
+                    path.set_extension("sh");
+
+                    steps.push(PlanStep::WriteFile {
+                        path,
+                        contents: script.contents.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Lay out the steps that `build` would take,
+    /// in order, without changing anything on disk.
+    ///
+    /// Use it to review what a spec will do
+    /// (especially its post-build scripts)
+    /// before building it.
+    ///
+    // TODO: example
+    pub fn plan(&mut self) -> BuilderResult<Vec<PlanStep>> {
+        let mut steps: Vec<PlanStep> = Vec::new();
+
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+        let rom_path = repo_dir.join(format!("baserom.{}.z64", self.spec.rom.region.to_string()));
+        let make_cmd = self.spec.get_make_cmd(&repo_dir);
+        let build_dir = get_build_dir(&self.spec, &repo_dir);
+
+        let copy_rom_step = match self.spec.rom.format {
+            RomType::BigEndian => PlanStep::Copy {
+                from: self.spec.rom.path.clone(),
+                to: rom_path.clone(),
+            },
+            format => PlanStep::ConvertRom {
+                from: self.spec.rom.path.clone(),
+                to: rom_path.clone(),
+                format,
+            },
+        };
+
+        for step in self.get_build_steps()? {
+            let stage = match step {
+                BuildStep::Setup(target) => {
+                    steps.push(PlanStep::Setup(target));
+                    self.plan_setup_stage(target, &copy_rom_step, &mut steps);
+                    continue;
+                }
+                BuildStep::Compile(compile_step) => {
+                    steps.push(PlanStep::Compile);
+
+                    match compile_step {
+                        CompileStep::UpToDate(executable_path) => {
+                            steps.push(PlanStep::Skip(format!(
+                                "the executable at {} is up to date",
+                                executable_path.display()
+                            )));
+                        }
+                        CompileStep::Compile => steps.push(PlanStep::RunCommand(make_cmd.clone())),
+                        CompileStep::CleanAndCompile => {
+                            steps.push(PlanStep::RunCommand(format!("{} clean", make_cmd)));
+                            steps.push(PlanStep::RunCommand(make_cmd.clone()));
+                        }
+                        CompileStep::DistCleanAndCompile => {
+                            steps.push(PlanStep::RunCommand(format!("{} distclean", make_cmd)));
+                            steps.push(PlanStep::Remove(repo_dir.join("build")));
+                            steps.push(PlanStep::RunCommand(make_cmd.clone()));
+                        }
+                    }
+                    continue;
+                }
+                BuildStep::PostBuild(stage, done) => {
+                    steps.push(PlanStep::PostBuild(stage));
+
+                    if done {
+                        steps.push(PlanStep::Skip("this stage is already done".to_owned()));
+                        continue;
+                    }
+
+                    stage
+                }
+            };
+
+            match stage {
+                TexturePack => {
+                    for pack in self.spec.get_texture_packs() {
+                        let to = build_dir.join("res").join("gfx");
+
+                        steps.push(match ArchiveKind::from_path(&pack.path) {
+                            Some(_) => PlanStep::Extract {
+                                archive: pack.path.clone(),
+                                to,
+                            },
+                            None => PlanStep::Copy {
+                                from: pack.path.join("gfx"),
+                                to,
+                            },
+                        });
+                    }
+                }
+                SoundPacks => {
+                    for pack in self.spec.sound_packs.iter().flatten() {
+                        let to = build_dir.join("res").join("sound");
+
+                        steps.push(match ArchiveKind::from_path(&pack.path) {
+                            Some(_) => PlanStep::Extract {
+                                archive: pack.path.clone(),
+                                to,
+                            },
+                            None => PlanStep::Copy {
+                                from: pack.path.join("sound"),
+                                to,
+                            },
+                        });
+                    }
+                }
+                LanguagePacks => {
+                    for pack in self.spec.language_packs.iter().flatten() {
+                        let to = build_dir.join("res").join("texts");
+
+                        steps.push(match ArchiveKind::from_path(&pack.path) {
+                            Some(_) => PlanStep::Extract {
+                                archive: pack.path.clone(),
+                                to,
+                            },
+                            None => PlanStep::Copy {
+                                from: pack.path.join("texts"),
+                                to,
+                            },
+                        });
+                    }
+                }
+                ModelPacks => {
+                    let packs = match &self.spec.model_packs {
+                        Some(p) => p,
+                        None => continue,
+                    };
+
+                    if !self.spec.repo.supports_dynos {
+                        steps.push(PlanStep::Skip(
+                            "this build does not support model packs, as they need DynOS"
+                                .to_owned(),
+                        ));
+                        continue;
+                    }
+
+                    for pack in packs {
+                        let to = pack.get_install_dir(&self.spec, &repo_dir);
+
+                        steps.push(match ArchiveKind::from_path(&pack.path) {
+                            Some(_) => PlanStep::Extract {
+                                archive: pack.path.clone(),
+                                to,
+                            },
+                            None => PlanStep::Copy {
+                                from: pack.path.clone(),
+                                to,
+                            },
+                        });
+                    }
+                }
+                CoopMods => {
+                    let mods = match &self.spec.coop_mods {
+                        Some(m) => m,
+                        None => continue,
+                    };
+
+                    if !self.spec.repo.supports_coop_mods {
+                        steps.push(PlanStep::Skip(
+                            "this build does not support coop mods".to_owned(),
+                        ));
+                        continue;
+                    }
+
+                    for coop_mod in mods {
+                        let to = if coop_mod.disabled {
+                            CoopMod::get_disabled_dir(&self.spec, &repo_dir)
+                        } else {
+                            CoopMod::get_mods_dir(&self.spec, &repo_dir)
+                        };
+
+                        steps.push(PlanStep::Copy {
+                            from: coop_mod.path.clone(),
+                            to: to.join(coop_mod.get_file_name()),
+                        });
+                    }
+                }
+                DynOSPacks => {
+                    let packs = match &self.spec.dynos_packs {
+                        Some(p) => p,
+                        None => continue,
+                    };
+
+                    if !self.spec.repo.supports_dynos {
+                        steps.push(PlanStep::Skip(
+                            "this build does not support DynOS packs".to_owned(),
+                        ));
+                        continue;
+                    }
+
+                    for pack in packs {
+                        let to = if pack.disabled {
+                            DynosPack::get_disabled_dir(&self.spec, &repo_dir)
+                        } else {
+                            DynosPack::get_packs_dir(&self.spec, &repo_dir)
+                        };
+
+                        steps.push(PlanStep::Copy {
+                            from: pack.path.clone(),
+                            to: to.join(pack.get_dir_name()),
+                        });
+                    }
+                }
+                PostBuildScripts => {
+                    for script in self.spec.scripts.iter().flatten() {
+                        steps.push(PlanStep::RunScript {
+                            name: script.name.clone(),
+                            description: script.description.clone(),
+                            contents: script.contents.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(steps)
     }
 
     /// Build the spec.
@@ -397,31 +1708,169 @@ impl<'a> Builder<'a> {
     /// builder.build();
     /// ```
     pub fn build(&mut self) -> BuilderResult<()> {
-        self.setup_build()?;
+        self.stage_timings.clear();
+
+        // a build that can't be saved to the
+        // history can still be built.
+        let entry = match start_build(&self.base_dir, SystemTime::now()) {
+            Ok((entry, log)) => {
+                *self.history_log.lock().unwrap() = Some(log);
+                Some(entry)
+            }
+            Err(e) => {
+                run_callback!(
+                    self.callbacks.log_cb,
+                    Warn,
+                    &format!("failed to save the build to the history: {}", e)
+                );
+                None
+            }
+        };
 
-        let executable_name = format!("sm64.{}.f3dex2e", self.spec.rom.region.to_string());
+        let result = self.run_build();
 
-        let executable_path = self
-            .base_dir
-            .join(&self.spec.repo.name)
-            .join("build")
-            .join(format!("{}_pc", self.spec.rom.region.to_string()))
-            .join(executable_name);
+        *self.history_log.lock().unwrap() = None;
+        let stage_timings = std::mem::take(&mut self.stage_timings);
 
-        if !executable_path.exists() {
-            self.compile();
-        } else {
+        let entry = match entry {
+            Some(e) => e,
+            None => return result,
+        };
+
+        if let Err(e) = finish_build(entry, &self.spec, stage_timings, &result) {
             run_callback!(
                 self.callbacks.log_cb,
-                LogType::Warn,
-                &format!(
-                    "not building the spec: the executable at {} already exists!",
-                    executable_path.display()
-                )
+                Warn,
+                &format!("failed to save the build to the history: {}", e)
             );
         }
 
-        self.post_build()?;
+        result
+    }
+
+    fn run_build(&mut self) -> BuilderResult<()> {
+        let steps = self.get_build_steps()?;
+
+        let needed_stages: Vec<SetupStage> = steps
+            .iter()
+            .filter_map(|step| match step {
+                BuildStep::Setup(stage) => Some(*stage),
+                _ => None,
+            })
+            .collect();
+        self.adopt_setup_stages(&needed_stages)?;
+
+        for step in steps {
+            match step {
+                BuildStep::Setup(stage) => self.run_setup_stage(stage)?,
+                BuildStep::Compile(compile_step) => self.run_compile_step(compile_step)?,
+                BuildStep::PostBuild(_, true) => (),
+                BuildStep::PostBuild(stage, false) => self.run_postbuild_stage(stage)?,
+            }
+        }
+
         Ok(())
     }
+
+    fn run_compile_step(&mut self, compile_step: CompileStep) -> BuilderResult<()> {
+        self.check_cancelled()?;
+
+        let repo_dir = self.base_dir.join(&self.spec.repo.name);
+
+        self.time_stage("compile the spec".to_owned(), |builder| {
+            match compile_step {
+                CompileStep::UpToDate(executable_path) => {
+                    run_callback!(
+                        builder.callbacks.log_cb,
+                        LogType::Warn,
+                        &format!(
+                            "not building the spec: the executable at {} is up to date!",
+                            executable_path.display()
+                        )
+                    );
+
+                    return Ok(());
+                }
+                CompileStep::DistCleanAndCompile => {
+                    run_callback!(
+                        builder.callbacks.log_cb,
+                        Info,
+                        "the ROM has changed, extracting the assets again"
+                    );
+
+                    // the assets that were extracted from the
+                    // old ROM have to go. the new one has
+                    // already been copied by its setup stage.
+                    builder.distclean()?;
+                }
+                CompileStep::CleanAndCompile => {
+                    run_callback!(
+                        builder.callbacks.log_cb,
+                        Info,
+                        "the make flags have changed, rebuilding from scratch"
+                    );
+
+                    builder.clean()?;
+                }
+                CompileStep::Compile => {
+                    // the packs may have to go on top
+                    // of freshly compiled files again.
+                    builder.state.reset_postbuild();
+                }
+            }
+
+            builder.compile()?;
+
+            if builder.spec.get_target() == Target::MinGW64 {
+                let dist_dir = get_dist_dir(&builder.base_dir);
+                let dlls = collect_windows_dlls(&builder.spec, &repo_dir, &dist_dir)?;
+                run_callback!(
+                    builder.callbacks.log_cb,
+                    Info,
+                    &format!(
+                        "copied the executable and {} DLL(s) to {}",
+                        dlls.len(),
+                        dist_dir.display()
+                    )
+                );
+            }
+
+            BuildFingerprint::new(&builder.spec, &repo_dir)?.save(&builder.base_dir)
+        })
+    }
+}
+
+/// Stops a build that was cancelled.
+///
+/// make is interrupted first, so that it deletes
+/// the targets that it was in the middle of
+/// building, and only killed if it hasn't stopped
+/// after `BUILD_STOP_TIMEOUT`.
+///
+/// Returns `true` if it had to be killed.
+fn stop_build(handle: &ReaderHandle, build_finished: &AtomicBool) -> bool {
+    // the build script leads the process
+    // group that make runs in.
+    let Some(&pid) = handle.pids().first() else {
+        return false;
+    };
+    let group = -(pid as libc::pid_t);
+
+    // SAFETY: kill only sends a signal.
+    unsafe { libc::kill(group, libc::SIGINT) };
+
+    let deadline = Instant::now() + BUILD_STOP_TIMEOUT;
+    while Instant::now() < deadline {
+        if build_finished.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // SAFETY: kill only sends a signal.
+    unsafe { libc::kill(group, libc::SIGKILL) };
+    let _ = handle.kill();
+
+    true
 }
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default)]
+/// A token to cancel a build with.
+///
+/// It can be cloned and handed to another thread
+/// (such as a GUI, or a control-c handler), and
+/// every clone cancels the same build. The builder
+/// checks it between stages, whilst cloning and
+/// whilst compiling, then rolls back and returns
+/// an `ErrorCause::Cancelled`.
+///
+// TODO: example
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new `CancellationToken`.
+    pub fn new() -> Self {
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Cancel the build.
+    ///
+    /// Can safely be called more than once.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if the build was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,91 @@
+use duct::cmd;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Cache statistics from `ccache`.
+pub struct CcacheStats {
+    /// Compilations that were
+    /// taken from the cache.
+    pub hits: u64,
+    /// Compilations that had
+    /// to run the compiler.
+    pub misses: u64,
+}
+
+impl CcacheStats {
+    /// Reads the statistics of a cache
+    /// directory, or of `ccache`'s default
+    /// one if there is none.
+    ///
+    /// Returns `None` if `ccache` can't
+    /// be run, or is too old to print
+    /// machine-readable statistics.
+    pub fn read(cache_dir: Option<&Path>) -> Option<Self> {
+        let mut stats_cmd = cmd!("ccache", "--print-stats")
+            .stdout_capture()
+            .stderr_null();
+
+        if let Some(cache_dir) = cache_dir {
+            stats_cmd = stats_cmd.env("CCACHE_DIR", cache_dir);
+        }
+
+        let output = stats_cmd.run().ok()?;
+        let mut stats = CcacheStats::default();
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let (key, value) = match line.split_once('\t') {
+                Some(kv) => kv,
+                None => continue,
+            };
+
+            let value: u64 = match value.trim().parse() {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            match key {
+                "direct_cache_hit" | "preprocessed_cache_hit" => stats.hits += value,
+                "cache_miss" => stats.misses += value,
+                _ => (),
+            }
+        }
+
+        Some(stats)
+    }
+
+    /// Get the statistics between an
+    /// earlier reading and this one.
+    ///
+    /// A shared cache counts the
+    /// builds of every instance.
+    pub fn since(&self, earlier: &CcacheStats) -> Self {
+        CcacheStats {
+            hits: self.hits.saturating_sub(earlier.hits),
+            misses: self.misses.saturating_sub(earlier.misses),
+        }
+    }
+
+    /// Get the share of compilations that
+    /// were taken from the cache, from 0 to 1.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.hits as f64 / total as f64
+    }
+}
+
+impl fmt::Display for CcacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.0}% hit rate)",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0
+        )
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::ErrorCause;
+use crate::prelude::builder_types::{BuilderResult, CleanStage};
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, Makeopt, Spec};
+use crate::util;
+
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+/// A fingerprint of everything that goes
+/// into a build, which is saved after every
+/// build to tell whether the next build has
+/// to recompile anything.
+pub struct BuildFingerprint {
+    /// The commit that the
+    /// repo is checked out at.
+    pub commit: Option<String>,
+    /// Every make flag that is passed
+    /// to make, including the platform's.
+    pub makeopts: Vec<Makeopt>,
+    /// The hash of the wrappers that
+    /// apply the compiler overrides.
+    #[serde(default)]
+    pub compiler_hash: String,
+    /// The hash of the ROM file.
+    pub rom_hash: String,
+}
+
+impl BuildFingerprint {
+    /// Creates a fingerprint of the
+    /// current inputs of a spec.
+    ///
+    // TODO: example
+    pub fn new<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> BuilderResult<Self> {
+        // a repo that can't be opened will be
+        // cloned again anyway, so it's not an error.
+        let commit = Repository::open(repo_dir.as_ref())
+            .and_then(|repo| {
+                let commit = repo.head()?.peel_to_commit()?;
+                Ok(commit.id().to_string())
+            })
+            .ok();
+
+        let mut makeopts = Makeopt::default_makeopts_for(spec.get_target());
+        if let Some(spec_makeopts) = &spec.makeopts {
+            makeopts.extend(spec_makeopts.iter().cloned());
+        }
+
+        let compiler_wrappers = spec
+            .compiler
+            .as_ref()
+            .map(|c| c.get_wrappers(spec.get_target()))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(program, script)| format!("{}\n{}", program, script))
+            .collect::<String>();
+
+        // specs without overrides match the
+        // fingerprints saved before the
+        // compiler was fingerprinted.
+        let compiler_hash = if compiler_wrappers.is_empty() {
+            String::new()
+        } else {
+            util::hash_str(compiler_wrappers)
+        };
+
+        let rom_hash = match util::hash_file(&spec.rom.path) {
+            Ok(h) => h,
+            Err(e) => {
+                let msg = format!("failed to hash {}", spec.rom.path.display());
+                return Err(err!(c_fs!(e, msg), "whilst fingerprinting the ROM"));
+            }
+        };
+
+        Ok(BuildFingerprint {
+            commit,
+            makeopts,
+            compiler_hash,
+            rom_hash,
+        })
+    }
+
+    /// Get the path that the fingerprint
+    /// of an instance is saved to.
+    pub fn get_path<P: AsRef<Path>>(base_dir: P) -> PathBuf {
+        util::get_smbuilder_dir(base_dir).join("fingerprint.yaml")
+    }
+
+    /// Loads the fingerprint of the
+    /// last build of an instance.
+    ///
+    /// Returns `None` if the instance has
+    /// not been built by smbuilder before,
+    /// or if the fingerprint is unreadable.
+    pub fn load<P: AsRef<Path>>(base_dir: P) -> Option<Self> {
+        let file_string = fs::read_to_string(Self::get_path(base_dir)).ok()?;
+        serde_yaml::from_str(&file_string).ok()
+    }
+
+    /// Saves the fingerprint as the
+    /// one of the last build.
+    pub fn save<P: AsRef<Path>>(&self, base_dir: P) -> BuilderResult<()> {
+        let path = Self::get_path(&base_dir);
+
+        if let Err(e) = fs::create_dir_all(util::get_smbuilder_dir(&base_dir)) {
+            return Err(err!(
+                c_fs!(e, "failed to create the smbuilder directory"),
+                "whilst saving the build fingerprint"
+            ));
+        }
+
+        let contents = match serde_yaml::to_string(self) {
+            Ok(s) => s,
+            Err(e) => {
+                return Err(err!(
+                    c_other!(e),
+                    "failed to serialize the build fingerprint"
+                ))
+            }
+        };
+
+        match fs::write(&path, contents) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let msg = format!("failed to write to {}", path.display());
+                Err(err!(c_fs!(e, msg), "whilst saving the build fingerprint"))
+            }
+        }
+    }
+
+    /// Checks how much of the build has to
+    /// be cleaned when going from `old` to
+    /// this fingerprint, if anything.
+    ///
+    /// Assets that were extracted from a
+    /// different ROM have to go, which takes
+    /// a `make distclean`. make can't tell
+    /// when make flags or the compiler have
+    /// changed, so those only take a
+    /// `make clean`. Changes to the
+    /// source code are picked up by make.
+    pub fn needs_clean(&self, old: &BuildFingerprint) -> Option<CleanStage> {
+        if self.rom_hash != old.rom_hash {
+            Some(CleanStage::DistClean)
+        } else if self.makeopts != old.makeopts || self.compiler_hash != old.compiler_hash {
+            Some(CleanStage::Clean)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use super::types::BuilderResult;
+
+use crate::callback_types::LogType;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, Spec};
+use crate::util;
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// How long a stage of a build took.
+pub struct StageTiming {
+    /// The stage, as it is
+    /// shown to the user.
+    pub stage: String,
+    /// How long it took, in
+    /// milliseconds.
+    pub duration_ms: u64,
+    /// Whether the stage succeeded.
+    pub succeeded: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// A build in the history of an instance.
+///
+/// Every build gets its own directory in
+/// `{base_dir}/.smbuilder/history`, with
+/// this summary, the full log of the build
+/// and the spec that it was built with.
+///
+// TODO: example
+pub struct BuildRecord {
+    /// The name of the build's directory,
+    /// which is unique to the instance.
+    pub id: String,
+    /// When the build started,
+    /// in seconds since the epoch.
+    pub started_at: u64,
+    /// How long the whole build
+    /// took, in milliseconds.
+    pub duration_ms: u64,
+    /// Whether the build succeeded.
+    pub succeeded: bool,
+    /// The error that the build failed
+    /// with, if it did fail.
+    pub error: Option<String>,
+    /// How long each stage took,
+    /// in the order they ran.
+    pub stages: Vec<StageTiming>,
+}
+
+impl BuildRecord {
+    /// Get the directory that the
+    /// history of an instance lives in.
+    pub fn get_history_dir<P: AsRef<Path>>(base_dir: P) -> PathBuf {
+        util::get_smbuilder_dir(base_dir).join("history")
+    }
+
+    /// Get the directory of this build.
+    pub fn get_dir<P: AsRef<Path>>(&self, base_dir: P) -> PathBuf {
+        Self::get_history_dir(base_dir).join(&self.id)
+    }
+
+    /// Lists the past builds of an
+    /// instance, newest first.
+    ///
+    /// Builds with an unreadable
+    /// summary are left out.
+    pub fn list<P: AsRef<Path>>(base_dir: P) -> BuilderResult<Vec<BuildRecord>> {
+        let history_dir = Self::get_history_dir(base_dir);
+
+        if !history_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let entries = match fs::read_dir(&history_dir) {
+            Ok(e) => e,
+            Err(e) => {
+                let msg = format!("failed to read {}", history_dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst listing the build history"));
+            }
+        };
+
+        let mut records: Vec<BuildRecord> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let summary = fs::read_to_string(entry.path().join("summary.yaml")).ok()?;
+                serde_yaml::from_str(&summary).ok()
+            })
+            .collect();
+
+        records.sort_by(|a, b| b.started_at.cmp(&a.started_at).then(b.id.cmp(&a.id)));
+
+        Ok(records)
+    }
+
+    /// Get the last build of an
+    /// instance that succeeded.
+    pub fn last_successful<P: AsRef<Path>>(base_dir: P) -> BuilderResult<Option<BuildRecord>> {
+        Ok(Self::list(base_dir)?.into_iter().find(|r| r.succeeded))
+    }
+
+    /// Reads the full log of this build.
+    pub fn read_log<P: AsRef<Path>>(&self, base_dir: P) -> BuilderResult<String> {
+        let log_path = self.get_dir(base_dir).join("build.log");
+
+        match fs::read_to_string(&log_path) {
+            Ok(log) => Ok(log),
+            Err(e) => {
+                let msg = format!("failed to read {}", log_path.display());
+                Err(err!(c_fs!(e, msg), "whilst reading the build log"))
+            }
+        }
+    }
+
+    /// Reads the spec that this
+    /// build was built with.
+    pub fn read_spec<P: AsRef<Path>>(&self, base_dir: P) -> BuilderResult<Spec> {
+        Spec::from_file(self.get_dir(base_dir).join("spec.yaml"))
+    }
+}
+
+/// A build that is being saved to the
+/// history of an instance whilst it runs.
+pub(crate) struct HistoryEntry {
+    id: String,
+    dir: PathBuf,
+    started_at: SystemTime,
+}
+
+/// Starts saving a build to the history
+/// of an instance, and returns the entry
+/// along with its log, which the lines of
+/// the build are written to as they come.
+pub(crate) fn start_build<P: AsRef<Path>>(
+    base_dir: P,
+    started_at: SystemTime,
+) -> BuilderResult<(HistoryEntry, LineWriter<File>)> {
+    let started_at_secs = get_secs_since_epoch(started_at);
+
+    let history_dir = BuildRecord::get_history_dir(&base_dir);
+    if let Err(e) = fs::create_dir_all(&history_dir) {
+        let msg = format!("failed to create {}", history_dir.display());
+        return Err(err!(c_fs!(e, msg), "whilst saving the build history"));
+    }
+
+    // two builds may start within
+    // the same second.
+    let mut id = started_at_secs.to_string();
+    let mut suffix = 1;
+    let dir = loop {
+        let dir = history_dir.join(&id);
+        match fs::create_dir(&dir) {
+            Ok(_) => break dir,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                id = format!("{}-{}", started_at_secs, suffix);
+                suffix += 1;
+            }
+            Err(e) => {
+                let msg = format!("failed to create {}", dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst saving the build history"));
+            }
+        }
+    };
+
+    let log_path = dir.join("build.log");
+    let log_file = match File::create(&log_path) {
+        Ok(f) => f,
+        Err(e) => {
+            let msg = format!("failed to create {}", log_path.display());
+            return Err(err!(c_fs!(e, msg), "whilst saving the build history"));
+        }
+    };
+
+    let entry = HistoryEntry {
+        id,
+        dir,
+        started_at,
+    };
+
+    Ok((entry, LineWriter::new(log_file)))
+}
+
+/// Writes a line that a build
+/// logged to its history.
+pub(crate) fn write_log_line(log: &mut LineWriter<File>, log_type: LogType, text: &str) {
+    // the log of the history is not worth
+    // stopping the build over.
+    let _ = writeln!(log, "[{}] {}", get_log_type_label(log_type), text);
+}
+
+/// Finishes saving a build to the history
+/// of an instance, and returns its record.
+pub(crate) fn finish_build(
+    entry: HistoryEntry,
+    spec: &Spec,
+    stages: Vec<StageTiming>,
+    result: &BuilderResult<()>,
+) -> BuilderResult<BuildRecord> {
+    let record = BuildRecord {
+        id: entry.id,
+        started_at: get_secs_since_epoch(entry.started_at),
+        duration_ms: entry
+            .started_at
+            .elapsed()
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        succeeded: result.is_ok(),
+        error: result.as_ref().err().map(get_plain_error),
+        stages,
+    };
+
+    let summary = match serde_yaml::to_string(&record) {
+        Ok(s) => s,
+        Err(e) => return Err(err!(c_other!(e), "failed to serialize the build summary")),
+    };
+
+    let spec_contents = match serde_yaml::to_string(spec) {
+        Ok(s) => s,
+        Err(e) => return Err(err!(c_other!(e), "failed to serialize the spec")),
+    };
+
+    let files = [
+        ("spec.yaml", spec_contents),
+        // the summary goes last, as builds
+        // without one are not listed.
+        ("summary.yaml", summary),
+    ];
+
+    for (file_name, contents) in files {
+        let path = entry.dir.join(file_name);
+        if let Err(e) = fs::write(&path, contents) {
+            let msg = format!("failed to write to {}", path.display());
+            return Err(err!(c_fs!(e, msg), "whilst saving the build history"));
+        }
+    }
+
+    Ok(record)
+}
+
+fn get_secs_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Get the message of an error without
+/// the colours that it is displayed with,
+/// as the history is not only read in a
+/// terminal.
+fn get_plain_error(e: &Error) -> String {
+    let mut message = match &e.description {
+        Some(d) => format!("{}: {}", e.cause, d),
+        None => e.cause.to_string(),
+    };
+
+    for hint in &e.hints {
+        message.push_str(&format!("\nhint: {}\n  fix: {}", hint.cause, hint.fix));
+    }
+
+    message
+}
+
+fn get_log_type_label(log_type: LogType) -> &'static str {
+    match log_type {
+        LogType::Error => "error",
+        LogType::Warn => "warn",
+        LogType::BuildOutput => "make",
+        LogType::Info => "info",
+    }
+}
@@ -5,46 +5,114 @@ pub mod types;
 /// takes care of all the building.
 pub mod builder;
 
+/// Locating the files that
+/// a build produces.
+pub mod artifacts;
+
+/// Fingerprints of the inputs of a
+/// build, for incremental rebuilds.
+pub mod fingerprint;
+
+/// Cooperative cancellation
+/// of a build.
+pub mod cancel;
+
+/// The progress of a build, which
+/// is saved to resume it later.
+pub mod state;
+
+/// Building many specs at once.
+pub mod queue;
+
+/// The history of the builds
+/// of an instance.
+pub mod history;
+
+/// Statistics from `ccache`.
+pub mod ccache;
+
 use crate::callback_types::LogType;
+use crate::diagnostics::get_compiler_output_file;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{builder_types::BuilderResult, err, Error};
 use crate::prelude::{run_callback, Callbacks, Region, Spec};
 
+use state::{get_setup_input_hash, BuildState};
 use types::SetupStage;
 use LogType::*;
 
-use std::path::Path;
+use git2::{build::CheckoutBuilder, Repository, ResetType, StatusOptions};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// Get the core setup tasks that are needed.
 ///
+/// A stage is needed if what it leaves behind
+/// is missing, or if the build state says that
+/// it never completed with the current inputs.
+/// Instances with no state yet are judged by
+/// what is on disk alone.
+///
 /// Returns a list of `SmbuilderSetupStage`.
 // TODO: example
 pub fn get_needed_setup_tasks<P: AsRef<Path>>(
     spec: &Spec,
     base_dir: P,
+    state: &BuildState,
     callbacks: &mut Callbacks,
-) -> Vec<SetupStage> {
+) -> BuilderResult<Vec<SetupStage>> {
     use SetupStage::*;
 
     let base_dir = base_dir.as_ref();
+    let repo_dir = base_dir.join(&spec.repo.name);
     let mut needed_stages: Vec<SetupStage> = Vec::new();
 
-    // check if the repo is cloned
-    if !base_dir.join(&spec.repo.name).exists() {
-        needed_stages.push(CloneRepo)
-    }
+    let mut is_needed = |stage: SetupStage, exists_on_disk: bool| -> BuilderResult<()> {
+        let interrupted = state.in_progress == Some(stage);
+        let outdated =
+            !state.is_empty() && !state.is_setup_done(stage, &get_setup_input_hash(spec, stage)?);
+
+        if !exists_on_disk || interrupted || outdated {
+            needed_stages.push(stage);
+        }
+
+        Ok(())
+    };
+
+    // check if the repo is cloned, a clone that
+    // was cut short may not even have a HEAD.
+    let repo_cloned = Repository::open(&repo_dir)
+        .map(|repo| is_finished_clone(&repo))
+        .unwrap_or(false);
+    is_needed(CloneRepo, repo_cloned)?;
 
     // check if the rom exists
-    if !base_dir
-        .join(&spec.repo.name)
+    let rom_exists = repo_dir
         .join(format!("baserom.{}.z64", spec.rom.region.to_string()))
-        .exists()
-    {
-        needed_stages.push(CopyRom)
-    }
+        .exists();
+    is_needed(CopyRom, rom_exists)?;
 
-    // check if the build script exists
-    if !base_dir.join("build.sh").exists() {
-        needed_stages.push(CreateBuildScript)
-    }
+    // check if the build script exists, and
+    // is up to date with the spec's make flags
+    let build_script_up_to_date = repo_dir.exists()
+        && fs::read_to_string(base_dir.join("build.sh"))
+            .map(|script| script == spec.to_script(&repo_dir))
+            .unwrap_or(false);
+    is_needed(CreateBuildScript, build_script_up_to_date)?;
+
+    // post-build script stuff
+    is_needed(CreateScriptsDir, get_scripts_dir(base_dir).exists())?;
+
+    // the paths of the scripts are not saved
+    // in the spec, so they have to be written
+    // on every build.
+    let scripts_written = spec
+        .scripts
+        .iter()
+        .flatten()
+        .all(|script| script.path.is_some());
+    is_needed(WritePostBuildScripts, scripts_written)?;
 
     // log
     let needed_stages_string = needed_stages
@@ -59,22 +127,151 @@ pub fn get_needed_setup_tasks<P: AsRef<Path>>(
         &format!("needed tasks: {}", needed_stages_string)
     );
 
-    // post-build script stuff
-    if !base_dir.join("scripts").exists() {
-        needed_stages.push(CreateScriptsDir)
+    // return
+    Ok(needed_stages)
+}
+
+/// Get the directory that the post-build
+/// scripts of an instance are written to.
+pub fn get_scripts_dir<P: AsRef<Path>>(base_dir: P) -> PathBuf {
+    base_dir.as_ref().join("scripts")
+}
+
+/// Hard-reset a repository to the revision that
+/// the spec pins it to, or to the tip of the
+/// spec's branch if it isn't pinned.
+pub fn checkout_pinned_revision(spec: &Spec, repo: &Repository) -> BuilderResult<()> {
+    let rev = match &spec.repo.rev {
+        Some(rev) => rev.clone(),
+        None => format!("origin/{}", spec.repo.branch),
+    };
+
+    let object = match repo.revparse_single(&rev) {
+        Ok(o) => o,
+        Err(e) => {
+            return Err(err!(
+                c_other!(e),
+                format!("failed to find the revision {} in the repository", rev)
+            ))
+        }
+    };
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+
+    match repo.reset(&object, ResetType::Hard, Some(&mut checkout)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(err!(
+            c_other!(e),
+            format!("failed to reset the repository to {}", rev)
+        )),
     }
+}
+
+/// Checks if a repository is a finished clone,
+/// one that got as far as checking out a HEAD.
+pub fn is_finished_clone(repo: &Repository) -> bool {
+    repo.head().is_ok()
+}
+
+/// Checks if any file in a repository
+/// that git tracks has been changed.
+pub fn has_local_changes(repo: &Repository) -> BuilderResult<bool> {
+    let mut status_options = StatusOptions::new();
+    status_options
+        .include_untracked(false)
+        .include_ignored(false);
 
-    if let Some(scripts) = &spec.scripts {
-        for script in scripts {
-            if script.path.is_none() {
-                needed_stages.push(WritePostBuildScripts);
-                continue;
-            }
+    match repo.statuses(Some(&mut status_options)) {
+        Ok(statuses) => Ok(!statuses.is_empty()),
+        Err(e) => Err(err!(
+            c_other!(e),
+            "failed to get the status of the repository"
+        )),
+    }
+}
+
+/// Get the files in a repository that are not
+/// tracked by git (and not ignored), relative
+/// to the root of the repository.
+pub fn get_untracked_files(repo: &Repository) -> BuilderResult<Vec<PathBuf>> {
+    let mut status_options = StatusOptions::new();
+    status_options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+
+    let statuses = match repo.statuses(Some(&mut status_options)) {
+        Ok(s) => s,
+        Err(e) => {
+            return Err(err!(
+                c_other!(e),
+                "failed to get the status of the repository"
+            ))
         }
+    };
+
+    let untracked_files = statuses
+        .iter()
+        .filter(|entry| entry.status().is_wt_new())
+        .filter_map(|entry| entry.path().map(PathBuf::from))
+        .collect();
+
+    Ok(untracked_files)
+}
+
+/// Check if a line of build output is a compiler
+/// invocation that builds a single object file,
+/// such as `cc -c -o build/us_pc/src/game/area.o src/game/area.c`.
+pub fn is_object_compile(line: &str) -> bool {
+    match get_compiler_output_file(line) {
+        Some(output) => output.ends_with(".o") && line.split_whitespace().any(|w| w == "-c"),
+        None => false,
     }
+}
 
-    // return
-    needed_stages
+/// Check if a line of build output marks the
+/// compilation of an object file, either as a
+/// compiler invocation or as a quiet make rule
+/// (`CC src/game/area.c`, `Compiling: src/game/area.c`).
+pub fn is_compile_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+
+    trimmed.starts_with("CC ")
+        || trimmed.starts_with("CXX ")
+        || trimmed.contains("Compiling:")
+        || is_object_compile(trimmed)
+}
+
+/// Get the source file that a line of build
+/// output is compiling, if it names one.
+///
+/// Ports may print a quiet make rule along
+/// with the compiler invocation, and both
+/// name the same source file.
+pub fn get_compiled_source(line: &str) -> Option<&str> {
+    line.split_whitespace()
+        .filter(|word| !word.starts_with('-'))
+        .find(|word| is_source_file(Path::new(word)))
+}
+
+/// Check if a file is a source file that
+/// gets compiled into an object file of
+/// its own.
+///
+/// Files such as `geo.inc.c` are included
+/// by others, rather than compiled.
+pub fn is_source_file(path: &Path) -> bool {
+    let file_name = match path.file_name() {
+        Some(n) => n.to_string_lossy(),
+        None => return false,
+    };
+
+    !file_name.ends_with(".inc.c")
+        && matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("c" | "cpp" | "s")
+        )
 }
 
 impl ToString for Region {
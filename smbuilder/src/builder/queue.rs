@@ -0,0 +1,348 @@
+use super::builder::Builder;
+use super::cancel::CancellationToken;
+use super::types::BuilderResult;
+
+use crate::callback_types::{LogType, QueueLogCb};
+use crate::prelude::{Callbacks, Spec};
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The result of a single build
+/// in a `BuildQueue`.
+pub struct QueuedBuildReport {
+    /// The name of the spec, or its
+    /// base directory if it has none.
+    pub name: String,
+    /// The base directory of the spec.
+    pub base_dir: PathBuf,
+    /// The amount of compile jobs
+    /// that the build was given.
+    pub jobs: u8,
+    /// Whether the build succeeded.
+    pub result: BuilderResult<()>,
+    /// Everything that the build logged.
+    pub log: Vec<(LogType, String)>,
+    /// How long the build took.
+    pub duration: Duration,
+}
+
+/// The results of every build
+/// in a `BuildQueue`, in the
+/// order that they were added.
+pub struct BuildReport {
+    /// The results of the builds.
+    pub builds: Vec<QueuedBuildReport>,
+}
+
+/// A queue of specs to build at once.
+///
+/// Every spec gets its own `Builder`. The
+/// repositories are cloned in parallel, then
+/// the specs are built a few at a time, with
+/// the CPU budget split between the builds
+/// that are left whenever one starts.
+///
+// TODO: example
+pub struct BuildQueue<'cb> {
+    builds: Vec<(Spec, PathBuf)>,
+
+    /// The amount of compile jobs that
+    /// all of the builds share.
+    pub cpu_budget: usize,
+
+    /// The amount of specs that
+    /// are compiled at once.
+    pub max_concurrent_builds: usize,
+
+    /// The amount of repositories
+    /// that are cloned at once.
+    pub max_parallel_clones: usize,
+
+    /// The token that cancels
+    /// every build in the queue.
+    pub cancel_token: CancellationToken,
+
+    log_cb: Option<Arc<Mutex<Box<QueueLogCb<'cb>>>>>,
+}
+
+struct QueueSlot<'cb> {
+    name: String,
+    base_dir: PathBuf,
+    requested_jobs: Option<u8>,
+    jobs: u8,
+    /// `None` if the builder couldn't be
+    /// created, with the error in `result`.
+    builder: Option<Builder<'cb>>,
+    log: Arc<Mutex<Vec<(LogType, String)>>>,
+    result: Option<BuilderResult<()>>,
+    duration: Duration,
+}
+
+impl<'cb> BuildQueue<'cb> {
+    /// Creates a new, empty `BuildQueue`.
+    ///
+    /// The CPU budget defaults to the
+    /// amount of CPUs on the machine.
+    pub fn new() -> Self {
+        let cpu_budget = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2);
+
+        BuildQueue {
+            builds: Vec::new(),
+            cpu_budget,
+            max_concurrent_builds: 2,
+            max_parallel_clones: 4,
+            cancel_token: CancellationToken::new(),
+            log_cb: None,
+        }
+    }
+
+    /// Adds a spec to the queue.
+    pub fn add<P: Into<PathBuf>>(mut self, spec: Spec, base_dir: P) -> Self {
+        self.builds.push((spec, base_dir.into()));
+        self
+    }
+
+    /// Sets the amount of compile jobs
+    /// that all of the builds share.
+    pub fn cpu_budget(mut self, cpu_budget: usize) -> Self {
+        self.cpu_budget = cpu_budget.max(1);
+        self
+    }
+
+    /// Sets the amount of specs
+    /// that are compiled at once.
+    pub fn max_concurrent_builds(mut self, max_concurrent_builds: usize) -> Self {
+        self.max_concurrent_builds = max_concurrent_builds.max(1);
+        self
+    }
+
+    /// Sets the amount of repositories
+    /// that are cloned at once.
+    pub fn max_parallel_clones(mut self, max_parallel_clones: usize) -> Self {
+        self.max_parallel_clones = max_parallel_clones.max(1);
+        self
+    }
+
+    /// Sets the token that cancels
+    /// every build in the queue.
+    pub fn with_cancel_token(mut self, cancel_token: CancellationToken) -> Self {
+        self.cancel_token = cancel_token;
+        self
+    }
+
+    /// Sets the log callback, which gets
+    /// the name of the build along with
+    /// every line that it logs.
+    pub fn log<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str, LogType, &str) + Send + Sync + 'cb,
+    {
+        self.log_cb = Some(Arc::new(Mutex::new(Box::new(callback))));
+        self
+    }
+
+    /// Get the amount of specs in the queue.
+    pub fn len(&self) -> usize {
+        self.builds.len()
+    }
+
+    /// Check if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.builds.is_empty()
+    }
+
+    /// Build every spec in the queue.
+    ///
+    /// A failed build doesn't stop the
+    /// others, check the report for the
+    /// result of each one.
+    pub fn run(self) -> BuildReport {
+        let concurrent_builds = self.max_concurrent_builds.clamp(1, self.len().max(1));
+        let cpu_budget = self.cpu_budget;
+
+        let mut slots: Vec<QueueSlot> = Vec::new();
+        for (spec, base_dir) in self.builds {
+            let name = spec
+                .name
+                .clone()
+                .unwrap_or_else(|| base_dir.display().to_string());
+            let requested_jobs = spec.jobs;
+
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let callbacks = get_slot_callbacks(&name, &log, &self.log_cb);
+
+            // a bad spec only fails its own build.
+            let (builder, result) = match Builder::new(spec, base_dir.clone(), callbacks) {
+                Ok(b) => (Some(b.with_cancel_token(self.cancel_token.clone())), None),
+                Err(e) => (None, Some(Err(e))),
+            };
+
+            slots.push(QueueSlot {
+                name,
+                base_dir,
+                requested_jobs,
+                jobs: 0,
+                builder,
+                log,
+                result,
+                duration: Duration::ZERO,
+            });
+        }
+
+        // cloning is bound by the network, so
+        // it doesn't eat into the CPU budget.
+        run_parallel(&mut slots, self.max_parallel_clones, |slot| {
+            let Some(builder) = slot.builder.as_mut() else {
+                return;
+            };
+
+            let start = Instant::now();
+
+            if let Err(e) = builder.clone_if_needed() {
+                slot.result = Some(Err(e));
+            }
+
+            slot.duration += start.elapsed();
+        });
+
+        // the builds that haven't finished share
+        // the CPU budget, so the ones that start
+        // last get more of it.
+        let builds_left = AtomicUsize::new(slots.iter().filter(|s| s.result.is_none()).count());
+
+        run_parallel(&mut slots, concurrent_builds, |slot| {
+            if slot.result.is_some() {
+                return;
+            }
+
+            let Some(builder) = slot.builder.as_mut() else {
+                return;
+            };
+
+            let sharing = builds_left
+                .load(Ordering::SeqCst)
+                .clamp(1, concurrent_builds);
+            let jobs_per_build = (cpu_budget / sharing).clamp(1, u8::MAX as usize) as u8;
+
+            // a spec that asks for
+            // less gets what it asks.
+            slot.jobs = slot
+                .requested_jobs
+                .unwrap_or(jobs_per_build)
+                .min(jobs_per_build);
+            builder.spec.jobs = Some(slot.jobs);
+
+            let start = Instant::now();
+            slot.result = Some(builder.build());
+            slot.duration += start.elapsed();
+
+            builds_left.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        let builds = slots
+            .into_iter()
+            .map(|slot| QueuedBuildReport {
+                name: slot.name,
+                base_dir: slot.base_dir,
+                jobs: slot.jobs,
+                result: slot.result.unwrap_or(Ok(())),
+                log: std::mem::take(&mut *slot.log.lock().unwrap()),
+                duration: slot.duration,
+            })
+            .collect();
+
+        BuildReport { builds }
+    }
+}
+
+impl<'cb> Default for BuildQueue<'cb> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildReport {
+    /// Checks if every build succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.builds.iter().all(|b| b.result.is_ok())
+    }
+
+    /// Get the builds that failed.
+    pub fn failed(&self) -> Vec<&QueuedBuildReport> {
+        self.builds.iter().filter(|b| b.result.is_err()).collect()
+    }
+}
+
+impl fmt::Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for build in &self.builds {
+            let secs = build.duration.as_secs();
+            let duration = format!("{}m {:02}s", secs / 60, secs % 60);
+
+            match &build.result {
+                Ok(_) => writeln!(
+                    f,
+                    "{}: built in {} with {} jobs",
+                    build.name, duration, build.jobs
+                )?,
+                Err(e) => writeln!(f, "{}: failed after {}: {}", build.name, duration, e)?,
+            }
+        }
+
+        write!(
+            f,
+            "{} built, {} failed",
+            self.builds.len() - self.failed().len(),
+            self.failed().len()
+        )
+    }
+}
+
+/// Get the callbacks of a single build, which
+/// keep its log, and pass it on to the queue's
+/// log callback.
+fn get_slot_callbacks<'cb>(
+    name: &str,
+    log: &Arc<Mutex<Vec<(LogType, String)>>>,
+    queue_log_cb: &Option<Arc<Mutex<Box<QueueLogCb<'cb>>>>>,
+) -> Callbacks<'cb> {
+    let name = name.to_owned();
+    let log = Arc::clone(log);
+    let queue_log_cb = queue_log_cb.clone();
+
+    Callbacks::new().log(move |log_type, text| {
+        log.lock().unwrap().push((log_type, text.to_owned()));
+
+        if let Some(callback) = &queue_log_cb {
+            callback.lock().unwrap()(&name, log_type, text);
+        }
+    })
+}
+
+/// Run a function on every item, on
+/// at most `threads` threads at once.
+fn run_parallel<T, F>(items: &mut [T], threads: usize, f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync,
+{
+    let items: Vec<Mutex<&mut T>> = items.iter_mut().map(Mutex::new).collect();
+    let next_item = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(item) = items.get(next_item.fetch_add(1, Ordering::SeqCst)) {
+                    f(&mut item.lock().unwrap());
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,335 @@
+use super::types::{BuilderResult, PostBuildStage, SetupStage};
+
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, Spec};
+use crate::util;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+/// A stage that has been completed,
+/// along with a hash of its inputs.
+pub struct StageRecord<S> {
+    /// The stage that was completed.
+    pub stage: S,
+    /// The hash of the inputs of
+    /// the stage when it completed.
+    pub input_hash: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// The progress of the build of an instance,
+/// which is saved after every stage so that
+/// an interrupted build picks up where it
+/// stopped.
+///
+// TODO: example
+pub struct BuildState {
+    /// The setup stages that
+    /// have been completed.
+    pub setup_stages: Vec<StageRecord<SetupStage>>,
+    /// The post-build stages that
+    /// have been completed.
+    pub postbuild_stages: Vec<StageRecord<PostBuildStage>>,
+    /// The setup stage that was started,
+    /// but has not been completed yet.
+    ///
+    /// If this is set when the state is
+    /// loaded, the stage was interrupted,
+    /// and whatever it left behind cannot
+    /// be trusted.
+    pub in_progress: Option<SetupStage>,
+}
+
+impl BuildState {
+    /// Get the path that the state
+    /// of an instance is saved to.
+    pub fn get_path<P: AsRef<Path>>(base_dir: P) -> PathBuf {
+        util::get_smbuilder_dir(base_dir).join("state.yaml")
+    }
+
+    /// Loads the state of an instance.
+    ///
+    /// Returns `None` if the instance has
+    /// not been built by smbuilder before,
+    /// or if the state is unreadable.
+    pub fn load<P: AsRef<Path>>(base_dir: P) -> Option<Self> {
+        let file_string = fs::read_to_string(Self::get_path(base_dir)).ok()?;
+        serde_yaml::from_str(&file_string).ok()
+    }
+
+    /// Saves the state of an instance.
+    pub fn save<P: AsRef<Path>>(&self, base_dir: P) -> BuilderResult<()> {
+        let path = Self::get_path(&base_dir);
+
+        if let Err(e) = fs::create_dir_all(util::get_smbuilder_dir(&base_dir)) {
+            return Err(err!(
+                c_fs!(e, "failed to create the smbuilder directory"),
+                "whilst saving the build state"
+            ));
+        }
+
+        let contents = match serde_yaml::to_string(self) {
+            Ok(s) => s,
+            Err(e) => return Err(err!(c_other!(e), "failed to serialize the build state")),
+        };
+
+        match fs::write(&path, contents) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let msg = format!("failed to write to {}", path.display());
+                Err(err!(c_fs!(e, msg), "whilst saving the build state"))
+            }
+        }
+    }
+
+    /// Checks if nothing has been
+    /// recorded in the state yet.
+    ///
+    /// This is the case for instances that
+    /// were set up by older versions of
+    /// smbuilder, too.
+    pub fn is_empty(&self) -> bool {
+        self.setup_stages.is_empty() && self.postbuild_stages.is_empty()
+    }
+
+    /// Checks if a setup stage has been
+    /// completed with the same inputs.
+    pub fn is_setup_done(&self, stage: SetupStage, input_hash: &str) -> bool {
+        self.setup_stages
+            .iter()
+            .any(|r| r.stage == stage && r.input_hash == input_hash)
+    }
+
+    /// Checks if a post-build stage has
+    /// been completed with the same inputs.
+    pub fn is_postbuild_done(&self, stage: PostBuildStage, input_hash: &str) -> bool {
+        self.postbuild_stages
+            .iter()
+            .any(|r| r.stage == stage && r.input_hash == input_hash)
+    }
+
+    /// Records a setup stage as completed,
+    /// replacing any older record of it.
+    pub fn complete_setup<S: ToString>(&mut self, stage: SetupStage, input_hash: S) {
+        self.setup_stages.retain(|r| r.stage != stage);
+        self.setup_stages.push(StageRecord {
+            stage,
+            input_hash: input_hash.to_string(),
+        });
+
+        if self.in_progress == Some(stage) {
+            self.in_progress = None;
+        }
+    }
+
+    /// Records a post-build stage as completed,
+    /// replacing any older record of it.
+    pub fn complete_postbuild<S: ToString>(&mut self, stage: PostBuildStage, input_hash: S) {
+        self.postbuild_stages.retain(|r| r.stage != stage);
+        self.postbuild_stages.push(StageRecord {
+            stage,
+            input_hash: input_hash.to_string(),
+        });
+    }
+
+    /// Forget about the completed post-build
+    /// stages, so that they run again.
+    ///
+    /// Call this whenever the build
+    /// directory is rebuilt or removed.
+    pub fn reset_postbuild(&mut self) {
+        self.postbuild_stages.clear();
+    }
+}
+
+/// Get the hash of the inputs of a setup stage.
+///
+/// When the hash changes, the stage is
+/// considered to be not done anymore.
+pub fn get_setup_input_hash(spec: &Spec, stage: SetupStage) -> BuilderResult<String> {
+    use SetupStage::*;
+
+    let inputs = match stage {
+        CloneRepo => format!(
+            "{}\n{}\n{}",
+            spec.repo.url,
+            spec.repo.branch,
+            spec.repo.rev.as_deref().unwrap_or_default()
+        ),
+        CopyRom => {
+            let rom_hash = match util::hash_file(&spec.rom.path) {
+                Ok(h) => h,
+                Err(e) => {
+                    let msg = format!("failed to hash {}", spec.rom.path.display());
+                    return Err(err!(c_fs!(e, msg), "whilst checking the ROM"));
+                }
+            };
+
+            format!("{}\n{}", rom_hash, spec.rom.region.to_string())
+        }
+        CreateBuildScript => {
+            let makeopts = spec
+                .makeopts
+                .iter()
+                .flatten()
+                .map(|m| format!("{}={}", m.key, m.value))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            let compiler_wrappers = spec
+                .compiler
+                .as_ref()
+                .map(|c| c.get_wrappers(spec.get_target()))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(program, script)| format!("{}\n{}", program, script))
+                .collect::<String>();
+
+            let ccache_dir = spec
+                .compiler
+                .as_ref()
+                .and_then(|c| c.ccache_dir.as_ref())
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default();
+
+            format!(
+                "{}\n{}\n{}\n{:?}\n{}",
+                makeopts,
+                spec.jobs.unwrap_or(2),
+                ccache_dir,
+                spec.get_target(),
+                compiler_wrappers
+            )
+        }
+        CreateScriptsDir => String::new(),
+        WritePostBuildScripts => get_scripts_inputs(spec),
+    };
+
+    Ok(util::hash_str(inputs))
+}
+
+/// Get the hash of the inputs
+/// of a post-build stage.
+pub fn get_postbuild_input_hash(spec: &Spec, stage: PostBuildStage) -> String {
+    use PostBuildStage::*;
+
+    let inputs = match stage {
+        TexturePack => spec
+            .get_texture_packs()
+            .iter()
+            .map(|pack| get_pack_inputs(&pack.name, &pack.path))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        SoundPacks => spec
+            .sound_packs
+            .iter()
+            .flatten()
+            .map(|pack| get_pack_inputs(&pack.name, &pack.path))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        LanguagePacks => {
+            let packs = spec
+                .language_packs
+                .iter()
+                .flatten()
+                .map(|pack| get_pack_inputs(&pack.name, &pack.path))
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            format!("{}\n{:?}", packs, spec.default_language)
+        }
+        ModelPacks => spec
+            .model_packs
+            .iter()
+            .flatten()
+            .map(|pack| get_pack_inputs(&pack.name, &pack.path))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        CoopMods => spec
+            .coop_mods
+            .iter()
+            .flatten()
+            .map(|coop_mod| {
+                format!(
+                    "{}\n{}",
+                    get_pack_inputs(&coop_mod.name, &coop_mod.path),
+                    coop_mod.disabled
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        DynOSPacks => spec
+            .dynos_packs
+            .iter()
+            .flatten()
+            .map(|pack| {
+                format!(
+                    "{}\n{}",
+                    get_pack_inputs(&pack.name, &pack.path),
+                    pack.disabled
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        PostBuildScripts => get_scripts_inputs(spec),
+    };
+
+    util::hash_str(inputs)
+}
+
+/// Get the inputs of a pack: its name, its
+/// path, and the size and the modification
+/// time of each of its files (or of the pack,
+/// if it is an archive), so that a pack that
+/// is changed in place is installed again.
+fn get_pack_inputs(name: &str, path: &Path) -> String {
+    let files = if path.is_dir() {
+        util::get_files_recursive(path).unwrap_or_default()
+    } else {
+        vec![PathBuf::new()]
+    };
+
+    let stamps = files
+        .iter()
+        .map(|file| {
+            let metadata = if file.as_os_str().is_empty() {
+                fs::metadata(path)
+            } else {
+                fs::metadata(path.join(file))
+            };
+
+            let (size, modified) = match metadata {
+                Ok(m) => (
+                    m.len(),
+                    m.modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                        .map(|d| d.as_nanos())
+                        .unwrap_or_default(),
+                ),
+                // it is missing, which the
+                // install will complain about.
+                Err(_) => (0, 0),
+            };
+
+            format!("{} {} {}", file.display(), size, modified)
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!("{}\n{}\n{}", name, path.display(), stamps)
+}
+
+fn get_scripts_inputs(spec: &Spec) -> String {
+    spec.scripts
+        .iter()
+        .flatten()
+        .map(|script| format!("{}\n{}", script.name, script.contents))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -1,4 +1,10 @@
+use super::ccache::CcacheStats;
 use crate::prelude::Error;
+use n64romconvert::RomType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// Error type.
 ///
@@ -6,7 +12,27 @@ use crate::prelude::Error;
 /// something more sensible.
 pub type BuilderResult<T> = Result<T, Error>;
 
+/// The amount of lines of build output that
+/// are kept around for error reporting.
+pub const COMPILE_OUTPUT_TAIL_LEN: usize = 20;
+
+/// How long make gets to stop after a build
+/// is cancelled, before it is killed.
+pub const BUILD_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
+/// The result of a successful compilation.
+pub struct CompileOutput {
+    /// The exit code of the build command.
+    pub status: i32,
+    /// The last few lines of the build output.
+    pub output_tail: Vec<String>,
+    /// What `ccache` did during the build,
+    /// if the spec uses it.
+    pub cache_stats: Option<CcacheStats>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 /// An enum to represent the different "setup stages"
 /// involved in building a port.
 ///
@@ -32,21 +58,131 @@ pub enum SetupStage {
     WritePostBuildScripts,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 /// An enum to represent the different post-build
 /// stages involved in building a port.
 ///
 /// Represents actions such as installing model
 /// packs and texture packs, running scripts, etc.
 pub enum PostBuildStage {
-    /// Install the texture pack
+    /// Install the texture pack(s)
     TexturePack,
+    /// Install the sound pack(s)
+    SoundPacks,
+    /// Install the language pack(s)
+    LanguagePacks,
+    /// Install the model pack(s)
+    ModelPacks,
+    /// Install the coop mod(s)
+    CoopMods,
     /// Install the DynOS packs(s)
     DynOSPacks,
     /// Run the Post-Build scripts
     PostBuildScripts,
 }
 
+#[derive(Debug)]
+/// An enum to represent the different
+/// stages involved in undoing a build.
+pub enum CleanStage {
+    /// Remove the compiled
+    /// files (`make clean`).
+    Clean,
+    /// Remove the compiled files and the
+    /// assets extracted from the ROM.
+    DistClean,
+    /// Reset the repository to the
+    /// pinned revision, dropping
+    /// patches and packs.
+    Reset,
+}
+
+#[derive(Clone, Debug)]
+/// A single step that a build would take,
+/// as laid out by `Builder::plan`.
+pub enum PlanStep {
+    /// A setup stage starts.
+    Setup(SetupStage),
+    /// The compilation starts.
+    Compile,
+    /// A post-build stage starts.
+    PostBuild(PostBuildStage),
+    /// Clone a repository.
+    Clone {
+        /// The URL of the repository.
+        url: String,
+        /// The branch to clone.
+        branch: String,
+        /// The revision that is
+        /// checked out afterwards.
+        rev: Option<String>,
+        /// Where it is cloned to.
+        dest: PathBuf,
+    },
+    /// Fetch a branch into a repository
+    /// that is already cloned, and check
+    /// it out.
+    Fetch {
+        /// The URL of the repository.
+        url: String,
+        /// The branch to fetch.
+        branch: String,
+        /// The revision that is
+        /// checked out afterwards.
+        rev: Option<String>,
+        /// The repository.
+        repo: PathBuf,
+    },
+    /// Copy a file or a directory.
+    Copy {
+        /// The source path.
+        from: PathBuf,
+        /// The target path.
+        to: PathBuf,
+    },
+    /// Extract an archive.
+    Extract {
+        /// The path of the archive.
+        archive: PathBuf,
+        /// The directory to extract it to.
+        to: PathBuf,
+    },
+    /// Convert a ROM to the z64 format.
+    ConvertRom {
+        /// The ROM to convert.
+        from: PathBuf,
+        /// Where the converted ROM goes.
+        to: PathBuf,
+        /// The format of the ROM.
+        format: RomType,
+    },
+    /// Create a directory.
+    CreateDir(PathBuf),
+    /// Remove a file or a directory.
+    Remove(PathBuf),
+    /// Write a file.
+    WriteFile {
+        /// The path of the file.
+        path: PathBuf,
+        /// What would be written.
+        contents: String,
+    },
+    /// Run a shell command.
+    RunCommand(String),
+    /// Run a post-build script.
+    RunScript {
+        /// The name of the script.
+        name: String,
+        /// The description of the script.
+        description: String,
+        /// What the script contains.
+        contents: String,
+    },
+    /// Nothing has to be done,
+    /// for the given reason.
+    Skip(String),
+}
+
 impl ToString for SetupStage {
     fn to_string(&self) -> String {
         use SetupStage::*;
@@ -68,7 +204,11 @@ impl ToString for PostBuildStage {
         use PostBuildStage::*;
 
         let result = match self {
-            TexturePack => "install the texture pack",
+            TexturePack => "install the texture pack(s)",
+            SoundPacks => "install the sound pack(s)",
+            LanguagePacks => "install the language pack(s)",
+            ModelPacks => "install the model pack(s)",
+            CoopMods => "install the coop mod(s)",
             DynOSPacks => "install the DynOS pack(s)",
             PostBuildScripts => "run the post-build script(s)",
         };
@@ -76,3 +216,82 @@ impl ToString for PostBuildStage {
         result.to_owned()
     }
 }
+
+impl fmt::Display for CleanStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CleanStage::*;
+
+        let result = match self {
+            Clean => "remove the compiled files",
+            DistClean => "remove the compiled files and extracted assets",
+            Reset => "reset the repository",
+        };
+
+        write!(f, "{}", result)
+    }
+}
+
+impl fmt::Display for PlanStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PlanStep::*;
+
+        match self {
+            Setup(stage) => write!(f, "stage: {}", stage.to_string()),
+            Compile => write!(f, "stage: compile the spec"),
+            PostBuild(stage) => write!(f, "stage: {}", stage.to_string()),
+            Clone {
+                url,
+                branch,
+                rev,
+                dest,
+            } => {
+                write!(f, "clone {} ({}) to {}", url, branch, dest.display())?;
+                match rev {
+                    Some(rev) => write!(f, ", at {}", rev),
+                    None => Ok(()),
+                }
+            }
+            Fetch {
+                url,
+                branch,
+                rev,
+                repo,
+            } => {
+                write!(f, "fetch {} ({}) into {}", url, branch, repo.display())?;
+                match rev {
+                    Some(rev) => write!(f, ", at {}", rev),
+                    None => Ok(()),
+                }
+            }
+            Copy { from, to } => write!(f, "copy {} to {}", from.display(), to.display()),
+            Extract { archive, to } => {
+                write!(f, "extract {} to {}", archive.display(), to.display())
+            }
+            ConvertRom { from, to, format } => write!(
+                f,
+                "convert the {:?} ROM at {} to {}",
+                format,
+                from.display(),
+                to.display()
+            ),
+            CreateDir(path) => write!(f, "create the directory {}", path.display()),
+            Remove(path) => write!(f, "remove {}", path.display()),
+            WriteFile { path, contents } => {
+                write!(f, "write {}:\n{}", path.display(), contents.trim_end())
+            }
+            RunCommand(command) => write!(f, "run `{}`", command),
+            RunScript {
+                name,
+                description,
+                contents,
+            } => write!(
+                f,
+                "run the script {} ({}):\n{}",
+                name,
+                description,
+                contents.trim_end()
+            ),
+            Skip(reason) => write!(f, "skip: {}", reason),
+        }
+    }
+}
@@ -1,7 +1,8 @@
 /// Houses callback types.
 pub mod types;
 
-use crate::builder_types::{PostBuildStage, SetupStage};
+use crate::builder_types::{CleanStage, PostBuildStage, SetupStage};
+use crate::diagnostics::types::Diagnostic;
 use types::*;
 
 /// Run a `Callbacks`
@@ -39,6 +40,9 @@ pub struct Callbacks<'cb> {
     /// A callback that is invoked
     /// on a new post-build stage.
     pub new_postbuild_stage_cb: Option<Box<NewPostBuildStageCb<'cb>>>,
+    /// A callback that is invoked
+    /// on a new clean stage.
+    pub new_clean_stage_cb: Option<Box<NewCleanStageCb<'cb>>>,
     /// A callback that is invoked when
     /// a new post-build script is being
     /// run.
@@ -47,6 +51,17 @@ pub struct Callbacks<'cb> {
     /// from git2's `RemoteCallbacks` to
     /// provide info on clone progress.
     pub repo_clone_progress_cb: Option<Box<RepoCloneProgressCb<'cb>>>,
+    /// A callback that provides an
+    /// estimate of the compilation
+    /// progress.
+    pub compile_progress_cb: Option<Box<CompileProgressCb<'cb>>>,
+    /// A callback that provides the
+    /// progress of installing a pack.
+    pub pack_install_progress_cb: Option<Box<PackInstallProgressCb<'cb>>>,
+    /// A callback that is invoked when
+    /// a compiler diagnostic is found in
+    /// the build output.
+    pub diagnostic_cb: Option<Box<DiagnosticCb<'cb>>>,
 }
 
 impl<'cb> Callbacks<'cb> {
@@ -56,8 +71,12 @@ impl<'cb> Callbacks<'cb> {
             log_cb: None,
             new_setup_stage_cb: None,
             new_postbuild_stage_cb: None,
+            new_clean_stage_cb: None,
             new_postbuild_script_cb: None,
             repo_clone_progress_cb: None,
+            compile_progress_cb: None,
+            pack_install_progress_cb: None,
+            diagnostic_cb: None,
         }
     }
 
@@ -103,6 +122,19 @@ impl<'cb> Callbacks<'cb> {
         self
     }
 
+    /// Set the new clean stage
+    /// callback.
+    ///
+    /// See the docs on `[NewCleanStageCb]`
+    /// for more information on arguments.
+    pub fn new_clean_stage<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(CleanStage) + Send + Sync + 'cb,
+    {
+        self.new_clean_stage_cb = Some(Box::new(callback) as Box<NewCleanStageCb<'cb>>);
+        self
+    }
+
     /// Set the repo clone progress
     /// callback.
     ///
@@ -118,6 +150,36 @@ impl<'cb> Callbacks<'cb> {
         self
     }
 
+    /// Set the compile progress
+    /// callback.
+    ///
+    /// See the docs on `CompileProgressCb`
+    /// for more information on arguments.
+    ///
+    // TODO: example
+    pub fn compile_progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(usize, usize) + Send + Sync + 'cb,
+    {
+        self.compile_progress_cb = Some(Box::new(callback) as Box<CompileProgressCb<'cb>>);
+        self
+    }
+
+    /// Set the pack install progress
+    /// callback.
+    ///
+    /// See the docs on `PackInstallProgressCb`
+    /// for more information on arguments.
+    ///
+    // TODO: example
+    pub fn pack_install_progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str, usize, usize) + Send + Sync + 'cb,
+    {
+        self.pack_install_progress_cb = Some(Box::new(callback) as Box<PackInstallProgressCb<'cb>>);
+        self
+    }
+
     /// Set the new post-build script
     /// callback.
     ///
@@ -132,4 +194,18 @@ impl<'cb> Callbacks<'cb> {
         self.new_postbuild_script_cb = Some(Box::new(callback) as Box<NewPostBuildScriptCb<'cb>>);
         self
     }
+
+    /// Set the diagnostic callback.
+    ///
+    /// See the docs on `[DiagnosticCb]`
+    /// for more information on arguments.
+    ///
+    // TODO: example
+    pub fn diagnostic<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Diagnostic) + Send + Sync + 'cb,
+    {
+        self.diagnostic_cb = Some(Box::new(callback) as Box<DiagnosticCb<'cb>>);
+        self
+    }
 }
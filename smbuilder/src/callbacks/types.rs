@@ -1,4 +1,5 @@
-use crate::prelude::builder_types::{PostBuildStage, SetupStage};
+use crate::prelude::builder_types::{CleanStage, PostBuildStage, SetupStage};
+use crate::prelude::diagnostic_types::Diagnostic;
 
 /// Callback for a log event.
 ///
@@ -19,6 +20,12 @@ pub type NewSetupStageCb<'cb> = dyn FnMut(SetupStage) + Send + Sync + 'cb;
 ///  * post-build stage
 pub type NewPostBuildStageCb<'cb> = dyn FnMut(PostBuildStage) + Send + Sync + 'cb;
 
+/// Callback for a new clean stage.
+///
+/// Args:
+///  * clean stage
+pub type NewCleanStageCb<'cb> = dyn FnMut(CleanStage) + Send + Sync + 'cb;
+
 /// Callback for when a new Post-Build script is run.
 ///
 /// Args:
@@ -34,6 +41,38 @@ pub type NewPostBuildScriptCb<'cb> = dyn FnMut(&str, &str) + Send + Sync + 'cb;
 ///  * recieved bytes
 pub type RepoCloneProgressCb<'cb> = dyn FnMut(usize, usize, usize) + Send + Sync + 'cb;
 
+/// Callback for compilation progress.
+///
+/// Args:
+///  * compiled object files
+///  * expected object files (`0` if unknown)
+pub type CompileProgressCb<'cb> = dyn FnMut(usize, usize) + Send + Sync + 'cb;
+
+/// Callback for pack installation progress.
+///
+/// Args:
+///  * name of the pack
+///  * installed files
+///  * total files
+pub type PackInstallProgressCb<'cb> = dyn FnMut(&str, usize, usize) + Send + Sync + 'cb;
+
+/// Callback for a compiler diagnostic
+/// found in the build output.
+///
+/// Args:
+///  * the diagnostic
+pub type DiagnosticCb<'cb> = dyn FnMut(&Diagnostic) + Send + Sync + 'cb;
+
+/// Callback for a log line of
+/// a build in a `BuildQueue`.
+///
+/// Args:
+///  * the name of the build
+///  * log type (error, warning, info, etc.)
+///  * text to log
+pub type QueueLogCb<'cb> = dyn FnMut(&str, LogType, &str) + Send + Sync + 'cb;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 /// An enum to represent
 /// a log type, for the
 /// log callback.
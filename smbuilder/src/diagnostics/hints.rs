@@ -0,0 +1,130 @@
+use crate::error::ErrorCause;
+use crate::prelude::builder_types::BuilderResult;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// The rules that ship with smbuilder.
+const BUILTIN_RULES: &str = include_str!("hints.yaml");
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// A rule that matches the signature
+/// of a known build failure.
+pub struct HintRule {
+    /// A unique name for the rule.
+    pub name: String,
+    /// Strings to look for in the build
+    /// output. The rule matches if any
+    /// of them are found in a line.
+    pub patterns: Vec<String>,
+    /// A human readable cause
+    /// of the failure.
+    pub cause: String,
+    /// A human readable way
+    /// to fix the failure.
+    pub fix: String,
+}
+
+#[derive(Clone, Debug)]
+/// A diagnosis of a build failure,
+/// from a matched `HintRule`.
+pub struct Hint {
+    /// The name of the rule
+    /// that matched.
+    pub rule: String,
+    /// The line of output that
+    /// the rule matched.
+    pub line: String,
+    /// The cause of the failure.
+    pub cause: String,
+    /// How to fix the failure.
+    pub fix: String,
+}
+
+#[derive(Clone, Debug, Default)]
+/// A set of `HintRule`s to
+/// match build output against.
+///
+// TODO: example
+pub struct HintRules {
+    /// The rules, in order.
+    pub rules: Vec<HintRule>,
+}
+
+impl HintRule {
+    /// Check if a line of build
+    /// output matches the rule.
+    pub fn matches(&self, line: &str) -> bool {
+        self.patterns.iter().any(|p| line.contains(p.as_str()))
+    }
+}
+
+impl HintRules {
+    /// Creates an empty set of rules.
+    pub fn new() -> Self {
+        HintRules { rules: Vec::new() }
+    }
+
+    /// Gets the rules that ship
+    /// with smbuilder.
+    pub fn builtin() -> Self {
+        let rules = serde_yaml::from_str::<Vec<HintRule>>(BUILTIN_RULES)
+            .unwrap_or_else(|e| panic!("failed to parse the built-in hint rules: {}", e));
+
+        HintRules { rules }
+    }
+
+    /// Loads a set of rules from
+    /// a yaml file.
+    ///
+    // TODO: example
+    pub fn from_file<P: AsRef<Path>>(path: P) -> BuilderResult<Self> {
+        let file_string = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("failed to read {}", path.as_ref().display());
+                return Err(err!(c_fs!(e, msg), "failed to read the hint rules"));
+            }
+        };
+
+        match serde_yaml::from_str::<Vec<HintRule>>(&file_string) {
+            Ok(rules) => Ok(HintRules { rules }),
+            Err(e) => Err(err!(c_other!(e), "failed to parse the hint rules")),
+        }
+    }
+
+    /// Adds rules to the set. Rules
+    /// with the same name as an existing
+    /// rule replace it.
+    pub fn extend(&mut self, other: HintRules) {
+        for rule in other.rules {
+            match self.rules.iter_mut().find(|r| r.name == rule.name) {
+                Some(existing) => *existing = rule,
+                None => self.rules.push(rule),
+            }
+        }
+    }
+
+    /// Gets the first rule that
+    /// matches a line of build output.
+    pub fn match_line(&self, line: &str) -> Option<Hint> {
+        let rule = self.rules.iter().find(|r| r.matches(line))?;
+
+        Some(Hint {
+            rule: rule.name.clone(),
+            line: line.to_owned(),
+            cause: rule.cause.clone(),
+            fix: rule.fix.clone(),
+        })
+    }
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (fix: {})", self.cause, self.fix)
+    }
+}
@@ -0,0 +1,332 @@
+/// Diagnostic types.
+pub mod types;
+
+/// Hints for known build
+/// failures.
+pub mod hints;
+
+use std::path::PathBuf;
+use types::*;
+
+/// Severities as printed by gcc and clang,
+/// in the order that they are looked for.
+const SEVERITY_MARKERS: [(&str, Severity); 4] = [
+    (": fatal error: ", Severity::Error),
+    (": error: ", Severity::Error),
+    (": warning: ", Severity::Warning),
+    (": note: ", Severity::Note),
+];
+
+/// Get the target that make failed to build from
+/// a line of build output, such as
+/// `make: *** [Makefile:330: build/us_pc/src/game/area.o] Error 1`.
+///
+/// Returns `None` if the line is not a make error.
+pub fn get_failed_make_target(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("*** [")?;
+    let (target, _) = rest.split_once(']')?;
+
+    // GNU make 4.x prefixes the target with
+    // the makefile and line number.
+    let target = match target.rsplit_once(": ") {
+        Some((_, t)) => t,
+        None => target,
+    };
+
+    Some(target.to_owned())
+}
+
+/// A parser that turns lines of build output
+/// into structured `Diagnostic`s.
+///
+/// It keeps track of the last object file that
+/// was being compiled, so that diagnostics from
+/// the compiler can be tied to a make target.
+///
+// TODO: example
+#[derive(Debug, Default)]
+pub struct DiagnosticParser {
+    current_target: Option<String>,
+}
+
+impl DiagnosticParser {
+    /// Creates a new `DiagnosticParser`.
+    pub fn new() -> Self {
+        DiagnosticParser {
+            current_target: None,
+        }
+    }
+
+    /// Parse a line of build output.
+    ///
+    /// Returns `None` if the line is not
+    /// a diagnostic.
+    pub fn parse_line(&mut self, line: &str) -> Option<Diagnostic> {
+        let line = line.trim_end();
+
+        if let Some(target) = get_compiler_output_file(line) {
+            self.current_target = Some(target);
+            return None;
+        }
+
+        if let Some(diagnostic) = parse_make_line(line) {
+            return Some(diagnostic);
+        }
+
+        if let Some(diagnostic) = parse_linker_line(line) {
+            return Some(Diagnostic {
+                target: self.current_target.clone(),
+                ..diagnostic
+            });
+        }
+
+        let (location, severity, message) = SEVERITY_MARKERS.iter().find_map(|(marker, sev)| {
+            line.split_once(marker)
+                .map(|(location, message)| (location, *sev, message))
+        })?;
+
+        let (file, line_no, column) = parse_location(location);
+
+        Some(Diagnostic {
+            file,
+            line: line_no,
+            column,
+            severity,
+            message: message.to_owned(),
+            target: self.current_target.clone(),
+        })
+    }
+}
+
+/// Get the output file of a compiler
+/// invocation echoed by make, such as
+/// `cc -c -o build/us_pc/src/game/area.o src/game/area.c`.
+///
+/// Returns `None` if the line is not
+/// a compiler invocation.
+pub fn get_compiler_output_file(line: &str) -> Option<String> {
+    let mut words = line.split_whitespace();
+    let program = words.next()?;
+
+    let program_name = program.rsplit('/').next().unwrap_or(program);
+    let is_compiler = ["cc", "gcc", "clang", "c++", "g++", "clang++"]
+        .iter()
+        .any(|c| program_name == *c || program_name.ends_with(&format!("-{}", c)));
+
+    if !is_compiler {
+        return None;
+    }
+
+    words
+        .skip_while(|word| *word != "-o")
+        .nth(1)
+        .map(|word| word.to_owned())
+}
+
+/// Parse errors that make itself prints,
+/// such as a failed target or a missing
+/// rule.
+fn parse_make_line(line: &str) -> Option<Diagnostic> {
+    let (_, message) = line.split_once("*** ")?;
+
+    let target = get_failed_make_target(line).or_else(|| {
+        // *** No rule to make target 'foo', needed by 'bar'.  Stop.
+        let (_, rest) = message.split_once("needed by '")?;
+        rest.split_once('\'').map(|(t, _)| t.to_owned())
+    });
+
+    let message = match message.split_once("] ") {
+        Some((_, m)) if message.starts_with('[') => m,
+        _ => message,
+    };
+
+    Some(Diagnostic {
+        file: None,
+        line: None,
+        column: None,
+        severity: Severity::Error,
+        message: message.trim_end_matches("  Stop.").to_owned(),
+        target,
+    })
+}
+
+/// Parse errors from the linker, such as
+/// `/usr/bin/ld: cannot find -lSDL2`.
+fn parse_linker_line(line: &str) -> Option<Diagnostic> {
+    let (program, message) = line.split_once(": ")?;
+    let program_name = program.rsplit('/').next().unwrap_or(program);
+
+    if program_name != "ld" && !program_name.ends_with("-ld") {
+        return None;
+    }
+
+    // the linker also points at the function that
+    // the next error is in (`/usr/bin/ld: build/foo.o:
+    // in function ...`), which isn't an error by itself.
+    if message.ends_with("':") && message.contains(": in function ") {
+        return None;
+    }
+
+    if message.starts_with("warning: ") {
+        return Some(Diagnostic {
+            file: None,
+            line: None,
+            column: None,
+            severity: Severity::Warning,
+            message: message.trim_start_matches("warning: ").to_owned(),
+            target: None,
+        });
+    }
+
+    Some(Diagnostic {
+        file: None,
+        line: None,
+        column: None,
+        severity: Severity::Error,
+        message: message.to_owned(),
+        target: None,
+    })
+}
+
+/// Split a `file:line:column` location into its
+/// parts. Tools such as `cc1` or `collect2` print
+/// their own name instead of a location.
+fn parse_location(location: &str) -> (Option<PathBuf>, Option<u32>, Option<u32>) {
+    let mut parts = location.rsplitn(3, ':').collect::<Vec<&str>>();
+    parts.reverse();
+
+    let numbers = parts
+        .iter()
+        .skip(1)
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Vec<Option<u32>>>();
+
+    match (parts.as_slice(), numbers.as_slice()) {
+        ([file, _, _], [Some(line), Some(column)]) => {
+            (Some(PathBuf::from(file)), Some(*line), Some(*column))
+        }
+        ([_, _, _], [_, Some(line)]) => {
+            // a path with a colon in it, and only a line
+            let (file, _) = location.rsplit_once(':').unwrap();
+            (Some(PathBuf::from(file)), Some(*line), None)
+        }
+        ([file, _], [Some(line)]) => (Some(PathBuf::from(file)), Some(*line), None),
+        _ if location.contains('.') || location.contains('/') => {
+            (Some(PathBuf::from(location)), None, None)
+        }
+        _ => (None, None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_compiler_errors_with_their_target() {
+        let mut parser = DiagnosticParser::new();
+
+        let compile_line = "cc -c -o build/us_pc/src/game/area.o src/game/area.c";
+        assert!(parser.parse_line(compile_line).is_none());
+
+        let diagnostic = parser
+            .parse_line("src/game/area.c:12:5: error: 'foo' undeclared")
+            .unwrap();
+
+        assert_eq!(diagnostic.file, Some(PathBuf::from("src/game/area.c")));
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, Some(5));
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.message, "'foo' undeclared");
+        assert_eq!(
+            diagnostic.target.as_deref(),
+            Some("build/us_pc/src/game/area.o")
+        );
+    }
+
+    #[test]
+    fn parses_fatal_errors_warnings_and_notes() {
+        let mut parser = DiagnosticParser::new();
+
+        let fatal = parser
+            .parse_line("src/game/area.h:3:10: fatal error: bar.h: No such file or directory")
+            .unwrap();
+        assert_eq!(fatal.severity, Severity::Error);
+        assert_eq!(fatal.message, "bar.h: No such file or directory");
+
+        let warning = parser.parse_line("src/a.c:7: warning: unused x").unwrap();
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(warning.line, Some(7));
+        assert_eq!(warning.column, None);
+
+        let note = parser
+            .parse_line("src/a.c:8:1: note: declared here")
+            .unwrap();
+        assert_eq!(note.severity, Severity::Note);
+    }
+
+    #[test]
+    fn parses_locations_without_a_file() {
+        let mut parser = DiagnosticParser::new();
+
+        let diagnostic = parser
+            .parse_line("cc1: warning: command-line option is not valid")
+            .unwrap();
+        assert_eq!(diagnostic.file, None);
+        assert_eq!(diagnostic.line, None);
+    }
+
+    #[test]
+    fn parses_make_errors() {
+        let mut parser = DiagnosticParser::new();
+
+        let failed = parser
+            .parse_line("make: *** [Makefile:330: build/us_pc/src/game/area.o] Error 1")
+            .unwrap();
+        assert_eq!(failed.message, "Error 1");
+        assert_eq!(
+            failed.target.as_deref(),
+            Some("build/us_pc/src/game/area.o")
+        );
+
+        let no_rule = parser
+            .parse_line("make: *** No rule to make target 'foo.c', needed by 'build/foo.o'.  Stop.")
+            .unwrap();
+        assert_eq!(
+            no_rule.message,
+            "No rule to make target 'foo.c', needed by 'build/foo.o'."
+        );
+        assert_eq!(no_rule.target.as_deref(), Some("build/foo.o"));
+    }
+
+    #[test]
+    fn parses_linker_errors() {
+        let mut parser = DiagnosticParser::new();
+
+        let error = parser
+            .parse_line("/usr/bin/ld: cannot find -lSDL2")
+            .unwrap();
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(error.message, "cannot find -lSDL2");
+
+        let function_line = "/usr/bin/ld: build/us_pc/src/pc/pc_main.o: in function `main':";
+        assert!(parser.parse_line(function_line).is_none());
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        let mut parser = DiagnosticParser::new();
+
+        assert!(parser.parse_line("Extracting assets...").is_none());
+        assert!(parser.parse_line("").is_none());
+    }
+
+    #[test]
+    fn gets_failed_make_targets() {
+        assert_eq!(
+            get_failed_make_target("make: *** [build/us_pc/sm64.us.f3dex2e] Error 2").as_deref(),
+            Some("build/us_pc/sm64.us.f3dex2e")
+        );
+        assert_eq!(get_failed_make_target("make: Nothing to be done"), None);
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The severity of a compiler
+/// diagnostic.
+pub enum Severity {
+    /// An error, which will
+    /// fail the build.
+    Error,
+    /// A warning.
+    Warning,
+    /// A note that gives more
+    /// context on a previous
+    /// diagnostic.
+    Note,
+}
+
+#[derive(Clone, Debug)]
+/// A structured diagnostic, parsed
+/// from a line of the output of the
+/// compiler, linker or make.
+pub struct Diagnostic {
+    /// The file the diagnostic
+    /// points at, if any.
+    pub file: Option<PathBuf>,
+    /// The line in the file.
+    pub line: Option<u32>,
+    /// The column in the line.
+    pub column: Option<u32>,
+    /// The severity of the diagnostic.
+    pub severity: Severity,
+    /// The message itself.
+    pub message: String,
+    /// The make target that was being
+    /// built when the diagnostic was
+    /// emitted, if it is known.
+    pub target: Option<String>,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Severity::*;
+
+        let result = match self {
+            Error => "error",
+            Warning => "warning",
+            Note => "note",
+        };
+
+        write!(f, "{}", result)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+            }
+
+            if let Some(column) = self.column {
+                write!(f, "{}:", column)?;
+            }
+
+            write!(f, " ")?;
+        }
+
+        write!(f, "{}: {}", self.severity, self.message)?;
+
+        if let Some(target) = &self.target {
+            write!(f, " (building {})", target)?;
+        }
+
+        Ok(())
+    }
+}
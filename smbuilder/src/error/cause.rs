@@ -31,14 +31,19 @@ pub enum ErrorCause {
         ctx: Option<AnyError>,
     },
     /// Indicates a failure in running the build command.
-    ///
-    /// the `duct` crate does not provide exit status
-    /// codes when running with `stderr_to_stdout`, which
-    /// is why little context can be provided.
     CompilationFailed {
-        /// The message that the program would like to give.
-        msg: &'static str,
+        /// The exit code of the build command, if
+        /// it exited normally.
+        status: Option<i32>,
+        /// The make target that failed to build,
+        /// if it could be found in the output.
+        target: Option<String>,
+        /// The last few lines of the build output.
+        output_tail: Vec<String>,
     },
+    /// Indicates that the build was cancelled
+    /// with a `CancellationToken`.
+    Cancelled,
     /// An error that doesnt apply to any of the variants
     Other {
         /// Context (cause, if any)
@@ -29,10 +29,24 @@ macro_rules! c_repo_clone {
 #[macro_export]
 /// Compilation failed error cause
 ///
-/// `msg: String`
+/// `status: Option<i32>, target: Option<String>, output_tail: Vec<String>`
 macro_rules! c_comp_failed {
-    ($msg:expr) => {
-        ErrorCause::CompilationFailed { msg: $msg }
+    ($status:expr, $target:expr, $output_tail:expr) => {
+        ErrorCause::CompilationFailed {
+            status: $status,
+            target: $target,
+            output_tail: $output_tail,
+        }
+    };
+}
+
+#[macro_export]
+/// Cancelled error cause
+///
+/// No arguments.
+macro_rules! c_cancelled {
+    () => {
+        ErrorCause::Cancelled
     };
 }
 
@@ -103,7 +117,7 @@ macro_rules! c_spawn_cmd {
     };
 }
 
-pub use {c_comp_failed, c_fs, c_other, c_repo_clone, c_spawn_cmd};
+pub use {c_cancelled, c_comp_failed, c_fs, c_other, c_repo_clone, c_spawn_cmd};
 
 #[macro_export]
 /// Instantiate an Error struct.
@@ -1,5 +1,6 @@
 mod cause;
 
+use crate::diagnostics::hints::Hint;
 pub use cause::*;
 use colored::Colorize;
 use std::fmt;
@@ -7,7 +8,7 @@ use std::fmt;
 /// Error macros to shortuct the creation of error types.
 pub mod macros;
 
-type AnyError = Box<dyn std::error::Error>;
+type AnyError = Box<dyn std::error::Error + Send + Sync>;
 
 #[derive(Debug)]
 /// An smbuilder-related error.
@@ -17,6 +18,10 @@ pub struct Error {
 
     /// The description of the error.
     pub description: Option<String>,
+
+    /// Diagnoses of known failures
+    /// that may have caused the error.
+    pub hints: Vec<Hint>,
 }
 
 macro_rules! fmt_anyerr {
@@ -56,7 +61,26 @@ impl fmt::Display for ErrorCause {
                     msg.clone().unwrap_or(String::new())
                 )
             }
-            C::CompilationFailed { msg } => write!(f, "compilation failed: {}", msg),
+            C::CompilationFailed {
+                status,
+                target,
+                output_tail: _,
+            } => {
+                let status = match status {
+                    Some(code) => format!("exit code {}", code),
+                    None => "terminated by a signal".to_owned(),
+                };
+
+                match target {
+                    Some(target) => write!(
+                        f,
+                        "compilation failed whilst building {} ({})",
+                        target, status
+                    ),
+                    None => write!(f, "compilation failed ({})", status),
+                }
+            }
+            C::Cancelled => write!(f, "the build was cancelled"),
             C::Other { ctx } => write!(f, "an unexpected error occured{}", fmt_anyerr!(ctx),),
         }
     }
@@ -65,7 +89,17 @@ impl fmt::Display for ErrorCause {
 impl Error {
     /// Creates a new `SmbuilderError`.
     pub fn new(cause: ErrorCause, description: Option<String>) -> Self {
-        Error { cause, description }
+        Error {
+            cause,
+            description,
+            hints: Vec::new(),
+        }
+    }
+
+    /// Attaches hints to the error.
+    pub fn with_hints(mut self, hints: Vec<Hint>) -> Self {
+        self.hints = hints;
+        self
     }
 }
 
@@ -77,7 +111,14 @@ impl fmt::Display for Error {
             format!("{}{}", "error: ".bold().red(), self.cause)
         };
 
-        write!(f, "{}", displayed_string)
+        write!(f, "{}", displayed_string)?;
+
+        for hint in &self.hints {
+            write!(f, "\n{}{}", "hint: ".bold().yellow(), hint.cause)?;
+            write!(f, "\n{}{}", "  fix: ".bold().yellow(), hint.fix)?;
+        }
+
+        Ok(())
     }
 }
 
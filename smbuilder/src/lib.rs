@@ -43,6 +43,18 @@ pub mod error;
 /// Build progress callbacks.
 pub mod callbacks;
 
+/// Structured compiler diagnostics,
+/// parsed from the build output.
+pub mod diagnostics;
+
+/// Checks for the toolchain and
+/// libraries needed to build a port.
+pub mod toolchain;
+
+/// Installing packs and other
+/// add-ons into a build.
+pub mod packs;
+
 /// Utility Functions.
 pub mod util;
 
@@ -0,0 +1,636 @@
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error};
+
+use duct::cmd;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The kinds of archives that
+/// packs can be installed from.
+pub enum ArchiveKind {
+    /// A `.zip` archive,
+    /// read with `unzip`.
+    Zip,
+    /// A tar archive, compressed or not
+    /// (`.tar`, `.tar.gz`, `.tar.xz`, etc.),
+    /// read with `tar`.
+    Tar,
+}
+
+impl ArchiveKind {
+    /// Get the kind of an archive
+    /// from its file name.
+    ///
+    /// Returns `None` if the path
+    /// is not an archive.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let file_name = path.as_ref().file_name()?.to_string_lossy().to_lowercase();
+
+        const TAR_SUFFIXES: [&str; 9] = [
+            ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tbz2", ".tar.zst", ".tzst",
+        ];
+
+        if file_name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if TAR_SUFFIXES.iter().any(|s| file_name.ends_with(s)) {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Lists the files and directories
+/// in an archive.
+pub fn list_entries<P: AsRef<Path>>(archive: P) -> BuilderResult<Vec<PathBuf>> {
+    let archive = archive.as_ref();
+    let kind = get_kind(archive)?;
+
+    let (program, expression) = match kind {
+        ArchiveKind::Zip => ("unzip", cmd!("unzip", "-Z1", archive)),
+        ArchiveKind::Tar => ("tar", cmd!("tar", "-tf", archive)),
+    };
+
+    let output = match expression.stdout_capture().stderr_capture().run() {
+        Ok(o) => o,
+        Err(e) => {
+            return Err(err!(
+                c_spawn_cmd!(
+                    program.to_owned(),
+                    format!("failed to list the files in {}", archive.display()),
+                    e
+                ),
+                "whilst reading the archive"
+            ))
+        }
+    };
+
+    let entries: Vec<PathBuf> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+
+    // an archive can't be trusted to
+    // stay inside of where it goes.
+    if let Some(entry) = entries.iter().find(|e| !is_safe_entry(e)) {
+        let inner_err = io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} contains a path outside of the archive: {}",
+                archive.display(),
+                entry.display()
+            ),
+        );
+        return Err(err!(c_fs!(inner_err), "whilst reading the archive"));
+    }
+
+    // nor can its links, which could
+    // point anywhere once extracted.
+    if let Some(entry) = get_special_entries(archive, kind)?.first() {
+        let inner_err = io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} contains a link or a special file: {}",
+                archive.display(),
+                entry
+            ),
+        );
+        return Err(err!(c_fs!(inner_err), "whilst reading the archive"));
+    }
+
+    Ok(entries)
+}
+
+/// Get the entries of an archive that aren't
+/// plain files or directories, like links,
+/// as `tar -tv` or `unzip -Z` list them.
+fn get_special_entries(archive: &Path, kind: ArchiveKind) -> BuilderResult<Vec<String>> {
+    let (program, expression) = match kind {
+        ArchiveKind::Zip => ("unzip", cmd!("unzip", "-Z", archive)),
+        ArchiveKind::Tar => ("tar", cmd!("tar", "-tvf", archive)),
+    };
+
+    let output = match expression.stdout_capture().stderr_capture().run() {
+        Ok(o) => o,
+        Err(e) => {
+            return Err(err!(
+                c_spawn_cmd!(
+                    program.to_owned(),
+                    format!("failed to list the files in {}", archive.display()),
+                    e
+                ),
+                "whilst reading the archive"
+            ))
+        }
+    };
+
+    let listing = String::from_utf8_lossy(&output.stdout);
+
+    // each entry starts with its mode,
+    // like `lrwxrwxrwx` for a link.
+    let entries = listing
+        .lines()
+        .filter(|line| match kind {
+            ArchiveKind::Tar => !line.is_empty() && !line.starts_with(['-', 'd']),
+            // the summary lines around the
+            // entries don't start with a mode.
+            ArchiveKind::Zip => {
+                let mut mode = line.chars();
+                matches!(mode.next(), Some('l' | 'p' | 'c' | 'b' | 's'))
+                    && matches!(mode.next(), Some('r' | '-'))
+            }
+        })
+        .map(str::to_owned)
+        .collect();
+
+    Ok(entries)
+}
+
+/// Find a directory in a list of archive
+/// entries, either at the top of the archive
+/// or nested one level deep (as in
+/// `My Pack/gfx`).
+///
+/// Returns its path inside of the archive.
+pub fn find_root(entries: &[PathBuf], dir_name: &str) -> Option<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+
+    for entry in entries {
+        let components: Vec<Component> = entry.components().collect();
+
+        // the directory itself may not be
+        // listed, only the files in it.
+        let root = match components.as_slice() {
+            [Component::Normal(name), _, ..] if *name == dir_name => PathBuf::from(name),
+            [Component::Normal(top), Component::Normal(name), _, ..] if *name == dir_name => {
+                Path::new(top).join(name)
+            }
+            _ => continue,
+        };
+
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    // the shallowest one wins, as the
+    // one at the top is not nested.
+    roots.into_iter().min_by_key(|r| r.components().count())
+}
+
+/// Extracts a directory of an archive
+/// (and everything in it) to `dest`,
+/// keeping its path inside of the
+/// archive.
+pub fn extract_dir<P, Q>(archive: P, dir: &Path, dest: Q) -> BuilderResult<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let archive = archive.as_ref();
+    let dest = dest.as_ref();
+
+    if let Err(e) = std::fs::create_dir_all(dest) {
+        let msg = format!("failed to create {}", dest.display());
+        return Err(err!(c_fs!(e, msg), "whilst extracting the archive"));
+    }
+
+    let (program, expression) = match get_kind(archive)? {
+        ArchiveKind::Zip => {
+            // unzip matches its own wildcards,
+            // so they aren't passed to a shell.
+            let pattern = format!("{}/*", dir.display());
+            (
+                "unzip",
+                cmd!("unzip", "-q", "-o", archive, pattern, "-d", dest),
+            )
+        }
+        ArchiveKind::Tar => ("tar", cmd!("tar", "-xf", archive, "-C", dest, dir)),
+    };
+
+    match expression.stdout_null().stderr_capture().run() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(err!(
+            c_spawn_cmd!(
+                program.to_owned(),
+                format!("failed to extract {}", archive.display()),
+                e
+            ),
+            "whilst extracting the archive"
+        )),
+    }
+}
+
+/// Extracts everything in an
+/// archive to `dest`.
+pub fn extract_all<P, Q>(archive: P, dest: Q) -> BuilderResult<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let archive = archive.as_ref();
+    let dest = dest.as_ref();
+
+    // listing it first checks that
+    // nothing in it escapes `dest`.
+    list_entries(archive)?;
+
+    if let Err(e) = std::fs::create_dir_all(dest) {
+        let msg = format!("failed to create {}", dest.display());
+        return Err(err!(c_fs!(e, msg), "whilst extracting the archive"));
+    }
+
+    let (program, expression) = match get_kind(archive)? {
+        ArchiveKind::Zip => ("unzip", cmd!("unzip", "-q", "-o", archive, "-d", dest)),
+        ArchiveKind::Tar => ("tar", cmd!("tar", "-xf", archive, "-C", dest)),
+    };
+
+    match expression.stdout_null().stderr_capture().run() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(err!(
+            c_spawn_cmd!(
+                program.to_owned(),
+                format!("failed to extract {}", archive.display()),
+                e
+            ),
+            "whilst extracting the archive"
+        )),
+    }
+}
+
+/// Reads the first `len` bytes of files
+/// in an archive, without extracting it.
+///
+/// Returns them by their paths inside of
+/// the archive. Files that are not in the
+/// archive are left out.
+pub fn read_file_heads<P: AsRef<Path>>(
+    archive: P,
+    files: &[PathBuf],
+    len: usize,
+) -> BuilderResult<HashMap<PathBuf, Vec<u8>>> {
+    let archive = archive.as_ref();
+
+    match get_kind(archive)? {
+        ArchiveKind::Zip => read_zip_file_heads(archive, files, len),
+        ArchiveKind::Tar => read_tar_file_heads(archive, files, len),
+    }
+}
+
+/// Reads the first `len` bytes of files
+/// in a zip archive, one at a time, as
+/// unzip can seek to each of them.
+fn read_zip_file_heads(
+    archive: &Path,
+    files: &[PathBuf],
+    len: usize,
+) -> BuilderResult<HashMap<PathBuf, Vec<u8>>> {
+    let mut heads = HashMap::new();
+
+    for file in files {
+        // unzip matches its own wildcards,
+        // which have to be escaped.
+        let pattern = file
+            .to_string_lossy()
+            .chars()
+            .map(|c| match c {
+                '*' | '?' | '[' => format!("[{}]", c),
+                c => c.to_string(),
+            })
+            .collect::<String>();
+
+        let output = match cmd!("unzip", "-p", archive, pattern)
+            .stdout_capture()
+            .stderr_null()
+            .unchecked()
+            .run()
+        {
+            Ok(o) => o,
+            Err(e) => {
+                return Err(err!(
+                    c_spawn_cmd!(
+                        "unzip".to_owned(),
+                        format!("failed to read {}", archive.display()),
+                        e
+                    ),
+                    "whilst reading the archive"
+                ))
+            }
+        };
+
+        if output.status.success() {
+            let mut head = output.stdout;
+            head.truncate(len);
+            heads.insert(file.clone(), head);
+        }
+    }
+
+    Ok(heads)
+}
+
+/// Reads the first `len` bytes of files in
+/// a tar archive, in one pass over it, as a
+/// compressed one can't be seeked through.
+fn read_tar_file_heads(
+    archive: &Path,
+    files: &[PathBuf],
+    len: usize,
+) -> BuilderResult<HashMap<PathBuf, Vec<u8>>> {
+    let whilst = "whilst reading the archive";
+    let read_err = |e: io::Error| {
+        let msg = format!("failed to read {}", archive.display());
+        err!(c_fs!(e, msg), whilst)
+    };
+
+    let file_name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let decompressor = [
+        (".gz", "gzip"),
+        (".tgz", "gzip"),
+        (".xz", "xz"),
+        (".txz", "xz"),
+        (".bz2", "bzip2"),
+        (".tbz2", "bzip2"),
+        (".zst", "zstd"),
+        (".tzst", "zstd"),
+    ]
+    .into_iter()
+    .find(|(suffix, _)| file_name.ends_with(suffix))
+    .map(|(_, program)| program);
+
+    let mut reader: Box<dyn Read> = match decompressor {
+        Some(program) => match cmd!(program, "-dc", archive).stderr_null().reader() {
+            Ok(r) => Box::new(r),
+            Err(e) => {
+                return Err(err!(
+                    c_spawn_cmd!(
+                        program.to_owned(),
+                        format!("failed to decompress {}", archive.display()),
+                        e
+                    ),
+                    whilst
+                ))
+            }
+        },
+        None => Box::new(File::open(archive).map_err(read_err)?),
+    };
+
+    let wanted: HashSet<&PathBuf> = files.iter().collect();
+    let mut heads = HashMap::new();
+    let mut long_name: Option<PathBuf> = None;
+    let mut header = [0u8; 512];
+
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(_) => (),
+            // some archives end without
+            // the empty blocks.
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(read_err(e)),
+        }
+
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+
+        let size = get_tar_entry_size(&header);
+        let padded_size = size.div_ceil(512) * 512;
+        let type_flag = header[156];
+
+        // long names come in an entry of their
+        // own, before the file that they name.
+        if matches!(type_flag, b'L' | b'x') {
+            let mut data = Vec::new();
+            (&mut reader)
+                .take(padded_size)
+                .read_to_end(&mut data)
+                .map_err(read_err)?;
+            data.truncate(size as usize);
+
+            long_name = if type_flag == b'L' {
+                Some(PathBuf::from(get_c_str(&data)))
+            } else {
+                get_pax_path(&data)
+            };
+            continue;
+        }
+
+        let name = long_name
+            .take()
+            .unwrap_or_else(|| get_tar_entry_name(&header));
+        let is_file = matches!(type_flag, b'0' | 0);
+
+        let mut head = Vec::new();
+        if is_file && wanted.contains(&name) {
+            (&mut reader)
+                .take(size.min(len as u64))
+                .read_to_end(&mut head)
+                .map_err(read_err)?;
+        }
+
+        io::copy(
+            &mut (&mut reader).take(padded_size - head.len() as u64),
+            &mut io::sink(),
+        )
+        .map_err(read_err)?;
+
+        if is_file && wanted.contains(&name) {
+            heads.insert(name, head);
+        }
+    }
+
+    // a decompressor that failed
+    // fails the read at the end.
+    io::copy(&mut reader, &mut io::sink()).map_err(read_err)?;
+
+    Ok(heads)
+}
+
+/// Get the size of a tar entry from its
+/// header, which is in octal, or in binary
+/// if it's too large for that.
+fn get_tar_entry_size(header: &[u8; 512]) -> u64 {
+    let field = &header[124..136];
+
+    if field[0] & 0x80 != 0 {
+        return field[1..]
+            .iter()
+            .fold(0, |size, byte| (size << 8) | *byte as u64);
+    }
+
+    let octal = get_c_str(field);
+    u64::from_str_radix(octal.trim(), 8).unwrap_or(0)
+}
+
+/// Get the name of a tar entry from its
+/// header, with the prefix of a ustar one.
+fn get_tar_entry_name(header: &[u8; 512]) -> PathBuf {
+    let name = get_c_str(&header[0..100]);
+
+    // GNU headers say `ustar  ` instead, and
+    // keep other fields where the prefix is.
+    if &header[257..263] == b"ustar\0" {
+        let prefix = get_c_str(&header[345..500]);
+        if !prefix.is_empty() {
+            return Path::new(&prefix).join(name);
+        }
+    }
+
+    PathBuf::from(name)
+}
+
+/// Get the path from the records of a pax
+/// header, which look like `30 path=...\n`.
+fn get_pax_path(data: &[u8]) -> Option<PathBuf> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|record| record.split_once(' ').map(|(_, r)| r))
+        .find_map(|record| record.strip_prefix("path="))
+        .map(PathBuf::from)
+}
+
+fn get_c_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn get_kind(archive: &Path) -> BuilderResult<ArchiveKind> {
+    match ArchiveKind::from_path(archive) {
+        Some(k) => Ok(k),
+        None => {
+            let inner_err = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a supported archive", archive.display()),
+            );
+            Err(err!(c_fs!(inner_err), "whilst reading the archive"))
+        }
+    }
+}
+
+fn is_safe_entry(entry: &Path) -> bool {
+    entry
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The magic and version
+    /// of a ustar header.
+    const USTAR_MAGIC: &[u8] = b"ustar\x0000";
+    /// The magic of a GNU header.
+    const GNU_MAGIC: &[u8] = b"ustar  \x00";
+
+    /// Makes a tar header for an entry, with
+    /// the magic of a ustar or GNU header.
+    fn make_header(name: &str, size: u64, type_flag: u8, magic: &[u8]) -> [u8; 512] {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+
+        let size = format!("{:011o}\0", size);
+        header[124..136].copy_from_slice(size.as_bytes());
+
+        header[156] = type_flag;
+        header[257..257 + magic.len()].copy_from_slice(magic);
+        header
+    }
+
+    #[test]
+    fn reads_the_prefix_of_ustar_names() {
+        let mut header = make_header("gfx/a.png", 0, b'0', USTAR_MAGIC);
+        header[345..353].copy_from_slice(b"My Pack/");
+
+        assert_eq!(
+            get_tar_entry_name(&header),
+            PathBuf::from("My Pack/gfx/a.png")
+        );
+    }
+
+    #[test]
+    fn ignores_the_prefix_field_of_gnu_headers() {
+        let mut header = make_header("gfx/a.png", 0, b'0', GNU_MAGIC);
+        // where GNU keeps the access time.
+        header[345..357].copy_from_slice(b"14657725135\0");
+
+        assert_eq!(get_tar_entry_name(&header), PathBuf::from("gfx/a.png"));
+    }
+
+    #[test]
+    fn reads_octal_and_binary_sizes() {
+        let header = make_header("a", 1234, b'0', USTAR_MAGIC);
+        assert_eq!(get_tar_entry_size(&header), 1234);
+
+        let mut header = make_header("a", 0, b'0', USTAR_MAGIC);
+        header[124..136].copy_from_slice(&[0x80, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 1]);
+        assert_eq!(get_tar_entry_size(&header), (2 << 24) + 1);
+    }
+
+    #[test]
+    fn reads_pax_paths() {
+        let data = b"27 mtime=1700000000.123456\n30 path=My Pack/gfx/a long.png\n";
+
+        assert_eq!(
+            get_pax_path(data),
+            Some(PathBuf::from("My Pack/gfx/a long.png"))
+        );
+        assert_eq!(get_pax_path(b"20 uid=1000\n"), None);
+    }
+
+    #[test]
+    fn reads_the_heads_of_files() {
+        let mut tar = Vec::new();
+
+        let mut add = |header: [u8; 512], data: &[u8]| {
+            tar.extend_from_slice(&header);
+            tar.extend_from_slice(data);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        };
+
+        add(make_header("gfx/", 0, b'5', USTAR_MAGIC), b"");
+        add(make_header("gfx/a.png", 5, b'0', USTAR_MAGIC), b"hello");
+        add(make_header("gfx/l.png", 0, b'2', USTAR_MAGIC), b"");
+        add(
+            make_header("././@LongLink", 14, b'L', GNU_MAGIC),
+            b"gfx/long.png\0\0",
+        );
+        add(make_header("gfx/long.p", 6, b'0', GNU_MAGIC), b"world!");
+        tar.extend_from_slice(&[0u8; 1024]);
+
+        let archive =
+            std::env::temp_dir().join(format!("smbuilder-test-{}.tar", std::process::id()));
+        std::fs::write(&archive, &tar).unwrap();
+
+        let files = [
+            PathBuf::from("gfx/a.png"),
+            PathBuf::from("gfx/l.png"),
+            PathBuf::from("gfx/long.png"),
+        ];
+        let heads = read_tar_file_heads(&archive, &files, 3);
+        std::fs::remove_file(&archive).unwrap();
+        let heads = heads.unwrap();
+
+        assert_eq!(heads.len(), 2);
+        assert_eq!(heads[Path::new("gfx/a.png")], b"hel");
+        assert_eq!(heads[Path::new("gfx/long.png")], b"wor");
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_the_archive() {
+        assert!(is_safe_entry(Path::new("gfx/a.png")));
+        assert!(is_safe_entry(Path::new("./gfx/a.png")));
+        assert!(!is_safe_entry(Path::new("../a.png")));
+        assert!(!is_safe_entry(Path::new("gfx/../../a.png")));
+        assert!(!is_safe_entry(Path::new("/etc/passwd")));
+    }
+}
@@ -0,0 +1,611 @@
+use super::layered::{copy_dir, move_file, remove_dir};
+use crate::builder::artifacts::get_build_dir;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, CoopMod, Error, Spec};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// The metadata of an sm64ex-coop mod,
+/// from the `-- name:` and
+/// `-- description:` comments at the
+/// top of its Lua file.
+pub struct CoopModMetadata {
+    /// The name that the mod
+    /// is shown with in game.
+    pub name: Option<String>,
+    /// The description of the mod.
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// What is kept with a build
+/// about its mods.
+struct CoopModRecord {
+    /// The mods that smbuilder installed,
+    /// which are the only ones that
+    /// it uninstalls.
+    #[serde(default)]
+    installed: Vec<String>,
+    /// Whether the mods that were enabled
+    /// or disabled by hand are enabled,
+    /// which is kept when the mods of the
+    /// spec are installed again.
+    #[serde(default)]
+    enabled: BTreeMap<String, bool>,
+}
+
+#[derive(Clone, Debug)]
+/// A mod in the mods directory of
+/// an sm64ex-coop build, or in the
+/// directory of the disabled mods.
+pub struct InstalledCoopMod {
+    /// The name of the mod's file
+    /// or directory.
+    pub file_name: String,
+    /// Where the mod is.
+    pub path: PathBuf,
+    /// Whether the game loads it.
+    pub enabled: bool,
+    /// The metadata of the mod.
+    pub metadata: CoopModMetadata,
+}
+
+impl CoopModMetadata {
+    /// Reads the metadata of a mod, from
+    /// the `.lua` file, or from the
+    /// `main.lua` of a mod directory.
+    pub fn read<P: AsRef<Path>>(mod_path: P) -> BuilderResult<Self> {
+        let lua_path = get_main_lua(mod_path.as_ref())?;
+
+        match fs::read_to_string(&lua_path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(e) => {
+                let msg = format!("failed to read {}", lua_path.display());
+                Err(err!(
+                    c_fs!(e, msg),
+                    "whilst reading the metadata of the mod"
+                ))
+            }
+        }
+    }
+
+    /// Parses the metadata from the
+    /// contents of a Lua file.
+    ///
+    /// Only the comments at the top of
+    /// the file are read, like the
+    /// game does.
+    pub fn parse(contents: &str) -> Self {
+        let mut metadata = Self::default();
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+
+            let comment = match line.strip_prefix("--") {
+                Some(c) => c.trim(),
+                None => break,
+            };
+
+            if let Some(name) = comment.strip_prefix("name:") {
+                metadata.name = Some(name.trim().to_owned());
+            } else if let Some(description) = comment.strip_prefix("description:") {
+                metadata.description = Some(description.trim().to_owned());
+            }
+        }
+
+        metadata
+    }
+}
+
+impl CoopMod {
+    /// Creates a new CoopMod.
+    ///
+    // TODO: example
+    pub fn new<S, P>(name: S, path: P) -> Self
+    where
+        S: ToString,
+        P: Into<PathBuf>,
+    {
+        CoopMod {
+            name: name.to_string(),
+            path: path.into(),
+            disabled: false,
+        }
+    }
+
+    /// Get the directory that the game
+    /// loads mods from, which is
+    /// `{build_dir}/mods`.
+    pub fn get_mods_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir).join("mods")
+    }
+
+    /// Get the directory that disabled
+    /// mods are kept in, which is
+    /// `{build_dir}/mods_disabled`.
+    pub fn get_disabled_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir).join("mods_disabled")
+    }
+
+    /// Get the name that the mod
+    /// is installed with, which is
+    /// the name of its file or
+    /// directory.
+    pub fn get_file_name(&self) -> String {
+        match self.path.file_name() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Reads the metadata of the mod.
+    pub fn read_metadata(&self) -> BuilderResult<CoopModMetadata> {
+        CoopModMetadata::read(&self.path)
+    }
+
+    /// Installs the mod into the mods
+    /// directory, or the directory of
+    /// the disabled mods if it is
+    /// disabled.
+    ///
+    /// If the mod is already installed,
+    /// it is replaced.
+    ///
+    // TODO: example
+    pub fn install<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        self.install_with_progress(spec, repo_dir, |_, _| {})
+    }
+
+    /// Installs the mod, like `install`,
+    /// calling `progress` with the amount of
+    /// installed files and the total amount of
+    /// files after each file.
+    ///
+    // TODO: example
+    pub fn install_with_progress<P, F>(
+        &self,
+        spec: &Spec,
+        repo_dir: P,
+        mut progress: F,
+    ) -> BuilderResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(usize, usize),
+    {
+        let repo_dir = repo_dir.as_ref();
+
+        // checks that it is a mod.
+        get_main_lua(&self.path)?;
+
+        let file_name = self.get_file_name();
+        let target_dir = if self.disabled {
+            Self::get_disabled_dir(spec, repo_dir)
+        } else {
+            Self::get_mods_dir(spec, repo_dir)
+        };
+
+        remove_installed(spec, repo_dir, &file_name)?;
+
+        if let Err(e) = fs::create_dir_all(&target_dir) {
+            let msg = format!("failed to create {}", target_dir.display());
+            return Err(err!(c_fs!(e, msg), "whilst installing the mod"));
+        }
+
+        let target = target_dir.join(&file_name);
+
+        if self.path.is_dir() {
+            copy_dir(&self.path, &target, progress)
+        } else {
+            match fs::copy(&self.path, &target) {
+                Ok(_) => {
+                    progress(1, 1);
+                    Ok(())
+                }
+                Err(e) => {
+                    let msg = format!(
+                        "failed to copy the mod from {} to {}",
+                        self.path.display(),
+                        target.display()
+                    );
+                    Err(err!(c_fs!(e, msg), "whilst installing the mod"))
+                }
+            }
+        }
+    }
+
+    /// Uninstalls the mod, whether
+    /// it is enabled or not.
+    ///
+    // TODO: example
+    pub fn remove<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        let file_name = self.get_file_name();
+
+        if !remove_installed(spec, repo_dir.as_ref(), &file_name)? {
+            return Err(not_installed_err(&file_name, "whilst removing the mod"));
+        }
+
+        Ok(())
+    }
+
+    /// Enables the mod, which moves it
+    /// back into the mods directory.
+    pub fn enable<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        set_coop_mod_enabled(spec, repo_dir, &self.get_file_name(), true)
+    }
+
+    /// Disables the mod, which moves it
+    /// out of the mods directory, without
+    /// deleting it.
+    pub fn disable<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        set_coop_mod_enabled(spec, repo_dir, &self.get_file_name(), false)
+    }
+}
+
+/// Enables or disables an installed mod,
+/// by the name of its file or directory.
+///
+/// Any mod in the mods directory can be
+/// disabled, including the ones that
+/// come with the game.
+///
+/// The change is kept with the build,
+/// so installing the mods of the spec
+/// again does not undo it.
+pub fn set_coop_mod_enabled<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+    file_name: &str,
+    enabled: bool,
+) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+
+    if !move_installed(spec, repo_dir, file_name, enabled)? {
+        return Err(not_installed_err(
+            file_name,
+            "whilst enabling or disabling the mod",
+        ));
+    }
+
+    let mut record = load_record(spec, repo_dir)?;
+    record.enabled.insert(file_name.to_owned(), enabled);
+    save_record(spec, repo_dir, &record)
+}
+
+/// Lists the mods of a build, enabled
+/// or not, sorted by their file names.
+pub fn list_coop_mods<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+) -> BuilderResult<Vec<InstalledCoopMod>> {
+    let dirs = [
+        (CoopMod::get_mods_dir(spec, &repo_dir), true),
+        (CoopMod::get_disabled_dir(spec, &repo_dir), false),
+    ];
+
+    let mut mods = Vec::new();
+
+    for (dir, enabled) in dirs {
+        if !dir.exists() {
+            continue;
+        }
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(e) => {
+                let msg = format!("failed to read {}", dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst listing the mods"));
+            }
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            // anything else in there isn't a mod.
+            let metadata = match CoopModMetadata::read(&path) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+
+            mods.push(InstalledCoopMod {
+                file_name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                path,
+                enabled,
+                metadata,
+            });
+        }
+    }
+
+    mods.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    Ok(mods)
+}
+
+/// Installs the mods of a spec, replacing
+/// the ones that were installed by an
+/// earlier call.
+///
+/// The mods that come with the game are
+/// left alone, and a mod with the same
+/// name as one of them is an error.
+///
+/// The mods that were enabled or disabled
+/// with `set_coop_mod_enabled` are kept
+/// that way, over what the spec says.
+///
+/// `progress` is called with the name of
+/// the mod, the amount of its files that
+/// are installed, and the total amount of
+/// its files.
+pub fn install_coop_mods<P, F>(
+    spec: &Spec,
+    repo_dir: P,
+    mods: &[CoopMod],
+    mut progress: F,
+) -> BuilderResult<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, usize, usize),
+{
+    let repo_dir = repo_dir.as_ref();
+
+    uninstall_coop_mods(spec, repo_dir)?;
+
+    let mut record = load_record(spec, repo_dir)?;
+
+    for coop_mod in mods {
+        let file_name = coop_mod.get_file_name();
+
+        if is_installed(spec, repo_dir, &file_name) {
+            let inner_err = io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("the game already has a mod called {}", file_name),
+            );
+            return Err(err!(c_fs!(inner_err), "whilst installing the mods"));
+        }
+
+        coop_mod.install_with_progress(spec, repo_dir, |done, total| {
+            progress(&coop_mod.name, done, total)
+        })?;
+
+        record.installed.push(file_name);
+        save_record(spec, repo_dir, &record)?;
+    }
+
+    for (file_name, enabled) in &record.enabled {
+        // it may have been removed since.
+        move_installed(spec, repo_dir, file_name, *enabled)?;
+    }
+
+    Ok(())
+}
+
+/// Uninstalls the mods that were
+/// installed by `install_coop_mods`.
+pub fn uninstall_coop_mods<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+    let mut record = load_record(spec, repo_dir)?;
+
+    for file_name in &record.installed {
+        // it may have been removed by hand.
+        remove_installed(spec, repo_dir, file_name)?;
+    }
+
+    record.installed.clear();
+    save_record(spec, repo_dir, &record)
+}
+
+/// Get the Lua file that the game runs
+/// for a mod, which also checks that the
+/// path is a mod.
+fn get_main_lua(mod_path: &Path) -> BuilderResult<PathBuf> {
+    let lua_path = if mod_path.is_dir() {
+        mod_path.join("main.lua")
+    } else {
+        mod_path.to_path_buf()
+    };
+
+    let is_lua = lua_path.extension().map(|e| e == "lua").unwrap_or(false);
+
+    if !is_lua || !lua_path.is_file() {
+        let inner_err = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} is not a .lua file, or a directory with a main.lua",
+                mod_path.display()
+            ),
+        );
+        return Err(err!(c_fs!(inner_err), "invalid mod"));
+    }
+
+    Ok(lua_path)
+}
+
+fn is_installed(spec: &Spec, repo_dir: &Path, file_name: &str) -> bool {
+    CoopMod::get_mods_dir(spec, repo_dir)
+        .join(file_name)
+        .exists()
+        || CoopMod::get_disabled_dir(spec, repo_dir)
+            .join(file_name)
+            .exists()
+}
+
+/// Removes a mod from both the mods directory
+/// and the disabled mods, and returns whether
+/// it was in either of them.
+fn remove_installed(spec: &Spec, repo_dir: &Path, file_name: &str) -> BuilderResult<bool> {
+    let mut was_installed = false;
+
+    for dir in [
+        CoopMod::get_mods_dir(spec, repo_dir),
+        CoopMod::get_disabled_dir(spec, repo_dir),
+    ] {
+        let path = dir.join(file_name);
+
+        if path.is_dir() {
+            remove_dir(&path)?;
+            was_installed = true;
+        } else if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                let msg = format!("failed to remove {}", path.display());
+                return Err(err!(c_fs!(e, msg), "whilst removing the mod"));
+            }
+            was_installed = true;
+        }
+    }
+
+    Ok(was_installed)
+}
+
+/// Moves a mod into the mods directory, or
+/// out of it, and returns whether it is
+/// installed at all.
+///
+/// If it is in both directories, such as a
+/// mod that the game copies back into the
+/// mods directory when it is built, the one
+/// on the wrong side is removed.
+fn move_installed(
+    spec: &Spec,
+    repo_dir: &Path,
+    file_name: &str,
+    enabled: bool,
+) -> BuilderResult<bool> {
+    let enabled_path = CoopMod::get_mods_dir(spec, repo_dir).join(file_name);
+    let disabled_path = CoopMod::get_disabled_dir(spec, repo_dir).join(file_name);
+
+    let (from, to) = if enabled {
+        (disabled_path, enabled_path)
+    } else {
+        (enabled_path, disabled_path)
+    };
+
+    if !from.exists() {
+        return Ok(to.exists());
+    }
+
+    if !to.exists() {
+        move_file(&from, &to)?;
+    } else if from.is_dir() {
+        remove_dir(&from)?;
+    } else if let Err(e) = fs::remove_file(&from) {
+        let msg = format!("failed to remove {}", from.display());
+        return Err(err!(c_fs!(e, msg), "whilst enabling or disabling the mod"));
+    }
+
+    Ok(true)
+}
+
+fn not_installed_err(file_name: &str, whilst: &str) -> Error {
+    let inner_err = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("the mod {} is not installed", file_name),
+    );
+
+    err!(c_fs!(inner_err), whilst)
+}
+
+fn get_record_path(spec: &Spec, repo_dir: &Path) -> PathBuf {
+    get_build_dir(spec, repo_dir).join("coop_mods.yaml")
+}
+
+fn load_record(spec: &Spec, repo_dir: &Path) -> BuilderResult<CoopModRecord> {
+    let path = get_record_path(spec, repo_dir);
+
+    if !path.exists() {
+        return Ok(CoopModRecord::default());
+    }
+
+    let file_string = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            let msg = format!("failed to read {}", path.display());
+            return Err(err!(c_fs!(e, msg), "whilst loading the installed mods"));
+        }
+    };
+
+    match serde_yaml::from_str(&file_string) {
+        Ok(record) => Ok(record),
+        Err(e) => Err(err!(c_other!(e), "failed to parse the installed mods")),
+    }
+}
+
+fn save_record(spec: &Spec, repo_dir: &Path, record: &CoopModRecord) -> BuilderResult<()> {
+    let path = get_record_path(spec, repo_dir);
+
+    if record.installed.is_empty() && record.enabled.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                let msg = format!("failed to remove {}", path.display());
+                Err(err!(c_fs!(e, msg), "whilst saving the installed mods"))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            let msg = format!("failed to create {}", parent.display());
+            return Err(err!(c_fs!(e, msg), "whilst saving the installed mods"));
+        }
+    }
+
+    let contents = match serde_yaml::to_string(record) {
+        Ok(s) => s,
+        Err(e) => return Err(err!(c_other!(e), "failed to serialize the installed mods")),
+    };
+
+    match fs::write(&path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let msg = format!("failed to write to {}", path.display());
+            Err(err!(c_fs!(e, msg), "whilst saving the installed mods"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_name_and_description() {
+        let contents =
+            "-- name: Cheats\n--description: Cheats for the\\#ff0000\\ host\n\nlocal x = 1\n";
+        let metadata = CoopModMetadata::parse(contents);
+
+        assert_eq!(metadata.name.as_deref(), Some("Cheats"));
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("Cheats for the\\#ff0000\\ host")
+        );
+    }
+
+    #[test]
+    fn only_reads_the_comments_at_the_top() {
+        let contents = "-- name: Cheats\nlocal x = 1\n-- description: not this\n";
+        let metadata = CoopModMetadata::parse(contents);
+
+        assert_eq!(metadata.name.as_deref(), Some("Cheats"));
+        assert_eq!(metadata.description, None);
+    }
+
+    #[test]
+    fn parses_files_without_metadata() {
+        assert_eq!(CoopModMetadata::parse(""), CoopModMetadata::default());
+        assert_eq!(
+            CoopModMetadata::parse("-- just a comment\nprint('hi')\n"),
+            CoopModMetadata::default()
+        );
+    }
+}
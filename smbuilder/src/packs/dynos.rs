@@ -0,0 +1,832 @@
+use super::layered::{copy_dir, move_file, remove_dir};
+use crate::builder::artifacts::get_build_dir;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, DynosPack, Error, Spec};
+use crate::util;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug)]
+/// A pack in the DynOS packs directory
+/// of a build, or in the directory of
+/// the disabled packs.
+pub struct InstalledDynosPack {
+    /// The name of the
+    /// pack's directory.
+    pub name: String,
+    /// Where the pack is.
+    pub path: PathBuf,
+    /// Whether the game loads it.
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+/// The result of checking the
+/// structure of a DynOS pack.
+///
+// TODO: example
+pub struct DynosPackReport {
+    /// The name of the pack.
+    pub name: String,
+    /// The models that the pack overrides,
+    /// named after their geo layouts (as
+    /// in `mario_geo`).
+    pub models: Vec<String>,
+    /// The compiled files that DynOS loads
+    /// (`.bin`, `.lvl`, `.tex` and `.bhv`).
+    pub files: Vec<PathBuf>,
+    /// The directories of models that
+    /// DynOS compiles itself, which have
+    /// a `geo.inc.c`.
+    pub source_dirs: Vec<PathBuf>,
+    /// Files that DynOS doesn't load.
+    pub unknown_files: Vec<PathBuf>,
+    /// The directory that the pack is
+    /// really in, if it is nested inside
+    /// of another one (as in `foo/foo`),
+    /// which would end up as
+    /// `packs/foo/foo` if it was
+    /// copied as it is.
+    pub nested_dir: Option<PathBuf>,
+}
+
+impl DynosPackReport {
+    /// Checks if the pack has anything
+    /// in it that DynOS loads.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.source_dirs.is_empty()
+    }
+
+    /// Checks if the pack can be copied
+    /// into the packs directory as it is.
+    pub fn is_valid(&self) -> bool {
+        !self.is_empty() && self.nested_dir.is_none()
+    }
+}
+
+impl fmt::Display for DynosPackReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(nested_dir) = &self.nested_dir {
+            writeln!(
+                f,
+                "nested: the pack is inside of {}, and is installed from there",
+                nested_dir.display()
+            )?;
+        }
+
+        for path in &self.unknown_files {
+            writeln!(f, "unknown file: {}", path.display())?;
+        }
+
+        if self.is_empty() {
+            return write!(f, "{} has nothing in it that DynOS loads", self.name);
+        }
+
+        write!(
+            f,
+            "{} overrides {} model(s): {}",
+            self.name,
+            self.models.len(),
+            self.models.join(", ")
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// What is kept with a build about
+/// its DynOS packs.
+pub(crate) struct DynosPackRecord {
+    /// The DynOS packs that smbuilder
+    /// installed, which are the only
+    /// ones that it uninstalls.
+    #[serde(default)]
+    pub installed: Vec<String>,
+    /// The model packs that smbuilder
+    /// installed, which are DynOS packs
+    /// too, but are uninstalled apart
+    /// from the others.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// The order that was set for the
+    /// packs, highest priority first,
+    /// which is kept when they are
+    /// installed again.
+    #[serde(default)]
+    pub order: Vec<String>,
+    /// The directories that packs were
+    /// renamed to, to give them their
+    /// priority, by the names of the packs.
+    #[serde(default)]
+    pub dir_names: BTreeMap<String, String>,
+}
+
+impl DynosPack {
+    /// Creates a new DynOS pack.
+    ///
+    // TODO: example
+    pub fn new<S, P>(name: S, path: P) -> Self
+    where
+        S: ToString,
+        P: Into<PathBuf>,
+    {
+        DynosPack {
+            name: name.to_string(),
+            path: path.into(),
+            disabled: false,
+        }
+    }
+
+    /// Get the directory that DynOS
+    /// loads packs from, which is
+    /// `{build_dir}/dynos/packs`.
+    pub fn get_packs_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir).join("dynos").join("packs")
+    }
+
+    /// Get the directory that disabled
+    /// packs are kept in, which is
+    /// `{build_dir}/dynos/packs_disabled`.
+    pub fn get_disabled_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir)
+            .join("dynos")
+            .join("packs_disabled")
+    }
+
+    /// Get the name that the pack
+    /// is installed with, which is
+    /// the name of its directory.
+    pub fn get_dir_name(&self) -> String {
+        match self.path.file_name() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Installs the DynOS pack (copies it
+    /// into the correct location), or into
+    /// the directory of the disabled packs
+    /// if it is disabled.
+    ///
+    /// If the pack is already installed,
+    /// it is replaced.
+    ///
+    // TODO: example
+    pub fn install<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        self.install_with_progress(spec, repo_dir, |_, _| {})
+    }
+
+    /// Installs the DynOS pack, like `install`,
+    /// calling `progress` with the amount of
+    /// installed files and the total amount of
+    /// files after each file.
+    ///
+    // TODO: example
+    pub fn install_with_progress<P, F>(
+        &self,
+        spec: &Spec,
+        repo_dir: P,
+        progress: F,
+    ) -> BuilderResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(usize, usize),
+    {
+        self.install_as(spec, repo_dir.as_ref(), &self.get_dir_name(), progress)
+    }
+
+    /// Installs the pack, like
+    /// `install_with_progress`, into a
+    /// directory called `dir_name`.
+    ///
+    /// A pack that is nested inside of
+    /// another directory is installed
+    /// from that directory. Anything else
+    /// is copied as it is, so use `inspect`
+    /// to check the pack first.
+    pub(crate) fn install_as<F>(
+        &self,
+        spec: &Spec,
+        repo_dir: &Path,
+        dir_name: &str,
+        progress: F,
+    ) -> BuilderResult<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        if !self.path.is_dir() {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("could not find the DynOS pack at {}", self.path.display()),
+            );
+            return Err(err!(c_fs!(inner_err), "invalid DynOS pack"));
+        }
+
+        let source_dir = match get_nested_dir(&self.path) {
+            Some(dir) => self.path.join(dir),
+            None => self.path.clone(),
+        };
+
+        let target_dir = if self.disabled {
+            Self::get_disabled_dir(spec, repo_dir)
+        } else {
+            Self::get_packs_dir(spec, repo_dir)
+        }
+        .join(dir_name);
+
+        remove_installed(spec, repo_dir, dir_name)?;
+
+        copy_dir(&source_dir, &target_dir, progress)?;
+
+        apply_order(spec, repo_dir)
+    }
+
+    /// Checks the structure of the pack, and
+    /// finds the models that it overrides.
+    ///
+    /// A pack that is nested inside of
+    /// another directory is still read,
+    /// from that directory.
+    ///
+    // TODO: example
+    pub fn inspect(&self) -> BuilderResult<DynosPackReport> {
+        if !self.path.is_dir() {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("could not find the DynOS pack at {}", self.path.display()),
+            );
+            return Err(err!(c_fs!(inner_err), "invalid DynOS pack"));
+        }
+
+        let nested_dir = get_nested_dir(&self.path);
+        let pack_dir = match &nested_dir {
+            Some(dir) => self.path.join(dir),
+            None => self.path.clone(),
+        };
+
+        let files = match util::get_files_recursive(&pack_dir) {
+            Ok(f) => f,
+            Err(e) => {
+                let msg = format!("failed to read {}", pack_dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst inspecting the DynOS pack"));
+            }
+        };
+
+        let mut report = DynosPackReport {
+            name: self.name.clone(),
+            nested_dir,
+            ..Default::default()
+        };
+
+        report.source_dirs = files
+            .iter()
+            .filter(|file| file.file_name().map(|n| n == GEO_SOURCE).unwrap_or(false))
+            .filter_map(|file| file.parent().map(Path::to_path_buf))
+            .collect();
+
+        for file in files {
+            // everything in a model's directory
+            // is read when DynOS compiles it.
+            if report.source_dirs.iter().any(|dir| file.starts_with(dir)) {
+                if file.file_name().map(|n| n == GEO_SOURCE).unwrap_or(false) {
+                    report.models.extend(get_geo_layouts(&pack_dir.join(&file)));
+                }
+                continue;
+            }
+
+            let extension = file
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+
+            match extension.as_str() {
+                "bin" => {
+                    if let Some(stem) = file.file_stem() {
+                        report.models.push(stem.to_string_lossy().to_string());
+                    }
+                    report.files.push(file);
+                }
+                "lvl" | "tex" | "bhv" => report.files.push(file),
+                _ => report.unknown_files.push(file),
+            }
+        }
+
+        report.models.sort();
+        report.models.dedup();
+        report.source_dirs.sort();
+        report.files.sort();
+        report.unknown_files.sort();
+
+        Ok(report)
+    }
+
+    /// Uninstalls the pack, whether
+    /// it is enabled or not.
+    ///
+    // TODO: example
+    pub fn remove<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        let dir_name = self.get_dir_name();
+
+        if !remove_installed(spec, repo_dir.as_ref(), &dir_name)? {
+            return Err(not_installed_err(
+                &dir_name,
+                "whilst removing the DynOS pack",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Enables the pack, which moves it
+    /// back into the packs directory.
+    pub fn enable<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        set_dynos_pack_enabled(spec, repo_dir, &self.get_dir_name(), true)
+    }
+
+    /// Disables the pack, which moves it
+    /// out of the packs directory, without
+    /// deleting it.
+    pub fn disable<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        set_dynos_pack_enabled(spec, repo_dir, &self.get_dir_name(), false)
+    }
+}
+
+/// Enables or disables an installed
+/// pack, by the name of its directory.
+pub fn set_dynos_pack_enabled<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+    name: &str,
+    enabled: bool,
+) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+    let record = load_record(spec, repo_dir)?;
+
+    let enabled_dir = DynosPack::get_packs_dir(spec, repo_dir);
+    let disabled_dir = DynosPack::get_disabled_dir(spec, repo_dir);
+
+    let (from_dir, to_dir) = if enabled {
+        (disabled_dir, enabled_dir)
+    } else {
+        (enabled_dir, disabled_dir)
+    };
+
+    if !find_installed(&record, &to_dir, name).is_empty() {
+        return Ok(());
+    }
+
+    let from = match find_installed(&record, &from_dir, name).into_iter().next() {
+        Some(p) => p,
+        None => {
+            return Err(not_installed_err(
+                name,
+                "whilst enabling or disabling the DynOS pack",
+            ))
+        }
+    };
+
+    // keeps the priority of the pack.
+    let to = to_dir.join(from.file_name().unwrap_or_default());
+
+    move_file(&from, &to)
+}
+
+/// Lists the DynOS packs of a build, enabled
+/// or not, in the order that was set for them,
+/// then in the order that they were installed
+/// in. Packs without one come after the others,
+/// sorted by their names.
+///
+/// The packs that were renamed to give
+/// them their priority are named without
+/// it.
+pub fn list_dynos_packs<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+) -> BuilderResult<Vec<InstalledDynosPack>> {
+    let repo_dir = repo_dir.as_ref();
+    let record = load_record(spec, repo_dir)?;
+
+    let dirs = [
+        (DynosPack::get_packs_dir(spec, repo_dir), true),
+        (DynosPack::get_disabled_dir(spec, repo_dir), false),
+    ];
+
+    let mut packs = Vec::new();
+
+    for (dir, enabled) in dirs {
+        if !dir.exists() {
+            continue;
+        }
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(e) => {
+                let msg = format!("failed to read {}", dir.display());
+                return Err(err!(c_fs!(e, msg), "whilst listing the DynOS packs"));
+            }
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !path.is_dir() {
+                continue;
+            }
+
+            let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            packs.push(InstalledDynosPack {
+                name: get_pack_name(&record, &dir_name),
+                path: path.clone(),
+                enabled,
+            });
+        }
+    }
+
+    let order = get_order(&record);
+
+    packs.sort_by_key(|pack| {
+        let position = order.iter().position(|name| *name == pack.name);
+        (position.unwrap_or(usize::MAX), pack.name.clone())
+    });
+
+    Ok(packs)
+}
+
+/// Sets the order of the DynOS packs of
+/// a build, highest priority first, by
+/// the names of their directories.
+///
+/// The directories of the packs are
+/// prefixed with their priority (as in
+/// `01-foo`), so that the game finds
+/// them in that order, which is also
+/// what it names them in its menu. The
+/// order is kept with the build, and is
+/// the order that `list_dynos_packs`
+/// returns them in.
+pub fn set_dynos_pack_order<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+    order: &[String],
+) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+
+    let installed: Vec<String> = list_dynos_packs(spec, repo_dir)?
+        .into_iter()
+        .map(|pack| pack.name)
+        .collect();
+
+    if let Some(name) = order.iter().find(|name| !installed.contains(name)) {
+        return Err(not_installed_err(name, "whilst ordering the DynOS packs"));
+    }
+
+    let mut record = load_record(spec, repo_dir)?;
+    record.order = order.to_vec();
+    save_record(spec, repo_dir, &record)?;
+
+    apply_order(spec, repo_dir)
+}
+
+/// Installs the DynOS packs of a spec, in
+/// order, replacing the ones that were
+/// installed by an earlier call.
+///
+/// An order that was set with
+/// `set_dynos_pack_order` comes first,
+/// and the spec only orders the rest.
+///
+/// `progress` is called with the name of
+/// the pack, the amount of its files that
+/// are installed, and the total amount of
+/// its files.
+pub fn install_dynos_packs<P, F>(
+    spec: &Spec,
+    repo_dir: P,
+    packs: &[DynosPack],
+    mut progress: F,
+) -> BuilderResult<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, usize, usize),
+{
+    let repo_dir = repo_dir.as_ref();
+
+    uninstall_dynos_packs(spec, repo_dir)?;
+
+    for pack in packs {
+        let dir_name = pack.get_dir_name();
+
+        // they would replace each other.
+        if load_record(spec, repo_dir)?.models.contains(&dir_name) {
+            return Err(name_taken_err(&dir_name, "a model pack"));
+        }
+
+        pack.install_with_progress(spec, repo_dir, |done, total| {
+            progress(&pack.name, done, total)
+        })?;
+
+        // loaded again, as installing the
+        // pack renames it in the record.
+        let mut record = load_record(spec, repo_dir)?;
+        record.installed.push(dir_name);
+        save_record(spec, repo_dir, &record)?;
+    }
+
+    apply_order(spec, repo_dir)
+}
+
+/// Uninstalls the DynOS packs that were
+/// installed by `install_dynos_packs`.
+pub fn uninstall_dynos_packs<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+
+    for name in load_record(spec, repo_dir)?.installed {
+        // it may have been removed by hand.
+        remove_installed(spec, repo_dir, &name)?;
+    }
+
+    // loaded again, as removing the
+    // packs forgets their renames.
+    let mut record = load_record(spec, repo_dir)?;
+    record.installed.clear();
+
+    save_record(spec, repo_dir, &record)
+}
+
+/// The file that a model's directory
+/// has to have, for DynOS to compile it.
+const GEO_SOURCE: &str = "geo.inc.c";
+
+/// Get the directory that a pack is really
+/// in, if all that the pack has is that one
+/// directory, and it isn't a model.
+fn get_nested_dir(pack_dir: &Path) -> Option<PathBuf> {
+    let entries: Vec<PathBuf> = fs::read_dir(pack_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+
+    match entries.as_slice() {
+        [only] if only.is_dir() && !only.join(GEO_SOURCE).exists() => {
+            only.file_name().map(PathBuf::from)
+        }
+        _ => None,
+    }
+}
+
+/// Get the names of the geo layouts
+/// in a `geo.inc.c`, which are the
+/// models that it overrides.
+fn get_geo_layouts(path: &Path) -> Vec<String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Vec::new(),
+    };
+
+    // as in `const GeoLayout mario_geo[] = {`
+    contents
+        .lines()
+        .filter_map(|line| line.split_once("GeoLayout "))
+        .filter_map(|(_, rest)| rest.split_once('['))
+        .map(|(name, _)| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Get the name of a pack from the name of
+/// its directory, which is the name that it
+/// had before it was renamed to give it its
+/// priority (as in `01-foo`), if it was.
+fn get_pack_name(record: &DynosPackRecord, dir_name: &str) -> String {
+    record
+        .dir_names
+        .iter()
+        .find(|(_, renamed)| *renamed == dir_name)
+        .map(|(name, _)| name.clone())
+        .unwrap_or_else(|| dir_name.to_owned())
+}
+
+/// Finds the directories of a pack in
+/// `dir`, whether it was renamed or not.
+fn find_installed(record: &DynosPackRecord, dir: &Path, name: &str) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
+            dir_name == name || record.dir_names.get(name).is_some_and(|d| *d == dir_name)
+        })
+        .collect()
+}
+
+/// Get the order of the packs of a build: the
+/// order that was set for them, then the packs
+/// and the model packs that smbuilder installed,
+/// in the order that they were installed in.
+fn get_order(record: &DynosPackRecord) -> Vec<String> {
+    let mut order = record.order.clone();
+
+    for name in record.installed.iter().chain(&record.models) {
+        if !order.contains(name) {
+            order.push(name.clone());
+        }
+    }
+
+    order
+}
+
+/// Prefixes the directories of the packs with
+/// their priority, so that they sort in their
+/// order, and removes it from the packs that
+/// have no order. The renames are kept in the
+/// record, so that only the directories that
+/// were renamed are named without it.
+pub(crate) fn apply_order(spec: &Spec, repo_dir: &Path) -> BuilderResult<()> {
+    let mut record = load_record(spec, repo_dir)?;
+    let order = get_order(&record);
+    let width = order.len().to_string().len().max(2);
+    let mut priority = 0;
+
+    let packs = list_dynos_packs(spec, repo_dir)?;
+
+    // they may have been removed by hand.
+    record
+        .dir_names
+        .retain(|name, _| packs.iter().any(|pack| pack.name == *name));
+
+    for pack in packs {
+        let dir_name = if order.contains(&pack.name) {
+            priority += 1;
+            let dir_name = format!("{:0width$}-{}", priority, pack.name, width = width);
+            record.dir_names.insert(pack.name.clone(), dir_name.clone());
+            dir_name
+        } else {
+            record.dir_names.remove(&pack.name);
+            pack.name.clone()
+        };
+
+        let target = pack.path.with_file_name(dir_name);
+
+        if target != pack.path {
+            move_file(&pack.path, &target)?;
+        }
+    }
+
+    save_record(spec, repo_dir, &record)
+}
+
+/// Removes a pack from both the packs directory
+/// and the disabled packs, and returns whether
+/// it was in either of them.
+pub(crate) fn remove_installed(spec: &Spec, repo_dir: &Path, name: &str) -> BuilderResult<bool> {
+    let mut record = load_record(spec, repo_dir)?;
+    let mut was_installed = false;
+
+    for dir in [
+        DynosPack::get_packs_dir(spec, repo_dir),
+        DynosPack::get_disabled_dir(spec, repo_dir),
+    ] {
+        for path in find_installed(&record, &dir, name) {
+            remove_dir(&path)?;
+            was_installed = true;
+        }
+    }
+
+    if record.dir_names.remove(name).is_some() {
+        save_record(spec, repo_dir, &record)?;
+    }
+
+    Ok(was_installed)
+}
+
+/// Get the error for a pack that would
+/// be installed over `other`, which has
+/// the same name.
+pub(crate) fn name_taken_err(name: &str, other: &str) -> Error {
+    let inner_err = io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} is already installed as {}", name, other),
+    );
+
+    err!(c_fs!(inner_err), "whilst installing the pack")
+}
+
+fn not_installed_err(name: &str, whilst: &str) -> Error {
+    let inner_err = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("the DynOS pack {} is not installed", name),
+    );
+
+    err!(c_fs!(inner_err), whilst)
+}
+
+fn get_record_path(spec: &Spec, repo_dir: &Path) -> PathBuf {
+    get_build_dir(spec, repo_dir).join("dynos_packs.yaml")
+}
+
+pub(crate) fn load_record(spec: &Spec, repo_dir: &Path) -> BuilderResult<DynosPackRecord> {
+    let path = get_record_path(spec, repo_dir);
+
+    if !path.exists() {
+        return Ok(DynosPackRecord::default());
+    }
+
+    let file_string = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            let msg = format!("failed to read {}", path.display());
+            return Err(err!(c_fs!(e, msg), "whilst loading the DynOS packs"));
+        }
+    };
+
+    match serde_yaml::from_str(&file_string) {
+        Ok(record) => Ok(record),
+        Err(e) => Err(err!(
+            c_other!(e),
+            "failed to parse the installed DynOS packs"
+        )),
+    }
+}
+
+pub(crate) fn save_record(
+    spec: &Spec,
+    repo_dir: &Path,
+    record: &DynosPackRecord,
+) -> BuilderResult<()> {
+    let path = get_record_path(spec, repo_dir);
+
+    if record.installed.is_empty()
+        && record.models.is_empty()
+        && record.order.is_empty()
+        && record.dir_names.is_empty()
+    {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                let msg = format!("failed to remove {}", path.display());
+                Err(err!(c_fs!(e, msg), "whilst saving the DynOS packs"))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            let msg = format!("failed to create {}", parent.display());
+            return Err(err!(c_fs!(e, msg), "whilst saving the DynOS packs"));
+        }
+    }
+
+    let contents = match serde_yaml::to_string(record) {
+        Ok(s) => s,
+        Err(e) => return Err(err!(c_other!(e), "failed to serialize the DynOS packs")),
+    };
+
+    match fs::write(&path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let msg = format!("failed to write to {}", path.display());
+            Err(err!(c_fs!(e, msg), "whilst saving the DynOS packs"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gets_the_names_of_renamed_packs() {
+        let mut record = DynosPackRecord::default();
+        record
+            .dir_names
+            .insert("foo".to_owned(), "01-foo".to_owned());
+
+        assert_eq!(get_pack_name(&record, "01-foo"), "foo");
+    }
+
+    #[test]
+    fn keeps_the_names_of_packs_that_were_not_renamed() {
+        let mut record = DynosPackRecord::default();
+        record
+            .dir_names
+            .insert("foo".to_owned(), "01-foo".to_owned());
+
+        // a numbered prefix that smbuilder
+        // didn't add is part of the name.
+        assert_eq!(get_pack_name(&record, "10-bar"), "10-bar");
+        assert_eq!(get_pack_name(&record, "foo"), "foo");
+    }
+}
@@ -0,0 +1,139 @@
+use super::layered::LayeredPack;
+use crate::builder::artifacts::get_build_dir;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, LanguagePack, Spec};
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+impl LanguagePack {
+    /// Creates a new LanguagePack.
+    ///
+    // TODO: example
+    pub fn new<S, P>(name: S, path: P) -> Self
+    where
+        S: ToString,
+        P: Into<PathBuf>,
+    {
+        LanguagePack {
+            name: name.to_string(),
+            path: path.into(),
+        }
+    }
+}
+
+impl LayeredPack for LanguagePack {
+    const KIND: &'static str = "language pack";
+    const ROOT_DIR: &'static str = "texts";
+    const PREFIX: &'static str = "language_pack";
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the directory that language
+    /// packs are installed into, which
+    /// is `{build_dir}/res/texts`.
+    fn get_install_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir).join("res").join("texts")
+    }
+}
+
+/// Get the languages that a build
+/// has, in the order that render96ex
+/// loads them (by file name, see
+/// `alloc_languages` in its
+/// `src/text/text-loader.c`).
+///
+/// A language is named after its file
+/// in `res/texts`, without the `.json`.
+pub fn get_languages<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> BuilderResult<Vec<String>> {
+    let texts_dir = LanguagePack::get_install_dir(spec, repo_dir);
+
+    if !texts_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = match fs::read_dir(&texts_dir) {
+        Ok(e) => e,
+        Err(e) => {
+            let msg = format!("failed to read {}", texts_dir.display());
+            return Err(err!(c_fs!(e, msg), "whilst listing the languages"));
+        }
+    };
+
+    let mut languages: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|e| e == "json").unwrap_or(false))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+
+    languages.sort();
+
+    Ok(languages)
+}
+
+/// Makes a language the one that the
+/// game starts in, by setting it in the
+/// `sm64config.txt` of the build.
+///
+/// The game only reads that config when
+/// it is launched with the arguments
+/// from `get_launch_args`.
+///
+/// render96ex keeps the language as its
+/// position in `get_languages`, so this
+/// has to be set again whenever the
+/// languages change.
+pub fn set_default_language<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+    language: &str,
+) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+
+    let index = match get_languages(spec, repo_dir)?
+        .iter()
+        .position(|l| l == language)
+    {
+        Some(i) => i,
+        None => {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the build has no language called {}", language),
+            );
+            return Err(err!(
+                c_fs!(inner_err),
+                "whilst setting the default language"
+            ));
+        }
+    };
+
+    let config_path = get_build_dir(spec, repo_dir).join("sm64config.txt");
+
+    // the rest of the config
+    // is left as it is.
+    let mut lines: Vec<String> = fs::read_to_string(&config_path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| line.split_whitespace().next() != Some("language"))
+        .map(str::to_owned)
+        .collect();
+    lines.push(format!("language {}", index));
+
+    match fs::write(&config_path, lines.join("\n") + "\n") {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let msg = format!("failed to write to {}", config_path.display());
+            Err(err!(c_fs!(e, msg), "whilst setting the default language"))
+        }
+    }
+}
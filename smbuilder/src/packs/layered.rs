@@ -0,0 +1,828 @@
+use super::archive::{self, ArchiveKind};
+use super::store::PackStore;
+use crate::builder::artifacts::get_build_dir;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, Spec};
+use crate::util;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// A file that was installed by a pack.
+pub struct InstalledFile {
+    /// The path of the file, relative
+    /// to where the pack is installed.
+    pub path: PathBuf,
+    /// The name of the pack
+    /// that it came from.
+    pub pack: String,
+    /// The hash of the file
+    /// when it was installed.
+    pub hash: String,
+    /// Where the file that this one
+    /// overwrote was backed up to, if
+    /// it overwrote one.
+    pub backup: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// A file that a pack overwrote, which
+/// wasn't put back when the pack was
+/// removed, as the file that the pack
+/// installed over it had been changed.
+pub struct KeptBackup {
+    /// The path of the file, relative
+    /// to where the pack is installed.
+    pub path: PathBuf,
+    /// Where the file that the
+    /// pack overwrote is kept.
+    pub backup: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// A record of the files that the packs
+/// of one kind (texture packs, sound packs)
+/// installed in a build, and which pack
+/// each of them came from.
+///
+/// It is kept in the build directory,
+/// so it goes along with the packs.
+///
+// TODO: example
+pub struct PackManifest {
+    /// The installed files, in the order
+    /// they were installed.
+    ///
+    /// A file that several packs install
+    /// is listed once for each of them,
+    /// and the last one is on disk.
+    pub files: Vec<InstalledFile>,
+    /// The directories that the packs
+    /// created, relative to where they
+    /// are installed, which are the only
+    /// ones that are removed with them.
+    #[serde(default)]
+    pub created_dirs: Vec<PathBuf>,
+    /// The files that removed packs
+    /// overwrote, which couldn't be
+    /// put back.
+    #[serde(default)]
+    pub kept_backups: Vec<KeptBackup>,
+}
+
+impl PackManifest {
+    /// Loads a manifest.
+    ///
+    /// Returns an empty manifest if
+    /// there is none at the path.
+    pub fn load<P: AsRef<Path>>(path: P) -> BuilderResult<Self> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let file_string = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("failed to read {}", path.display());
+                return Err(err!(c_fs!(e, msg), "whilst loading the pack manifest"));
+            }
+        };
+
+        match serde_yaml::from_str(&file_string) {
+            Ok(m) => Ok(m),
+            Err(e) => Err(err!(c_other!(e), "failed to parse the pack manifest")),
+        }
+    }
+
+    /// Saves the manifest.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> BuilderResult<()> {
+        let path = path.as_ref();
+
+        let contents = match serde_yaml::to_string(self) {
+            Ok(s) => s,
+            Err(e) => return Err(err!(c_other!(e), "failed to serialize the pack manifest")),
+        };
+
+        match fs::write(path, contents) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let msg = format!("failed to write to {}", path.display());
+                Err(err!(c_fs!(e, msg), "whilst saving the pack manifest"))
+            }
+        }
+    }
+
+    /// Get the name of the pack that a
+    /// file (relative to where the packs
+    /// are installed) was installed by.
+    pub fn get_pack_of<P: AsRef<Path>>(&self, path: P) -> Option<&str> {
+        self.files
+            .iter()
+            .rev()
+            .find(|f| f.path == path.as_ref())
+            .map(|f| f.pack.as_str())
+    }
+
+    /// Get the names of the installed
+    /// packs, in the order they were
+    /// installed.
+    pub fn get_packs(&self) -> Vec<&str> {
+        let mut packs: Vec<&str> = Vec::new();
+
+        for file in &self.files {
+            if !packs.contains(&file.pack.as_str()) {
+                packs.push(&file.pack);
+            }
+        }
+
+        packs
+    }
+
+    /// Records a file as installed by a pack,
+    /// on top of any earlier pack.
+    pub fn record(&mut self, file: InstalledFile) {
+        self.files.push(file);
+    }
+}
+
+/// A kind of pack that is layered over the
+/// files of the port, like texture packs.
+///
+/// Every kind is installed and removed the
+/// same way, so each one only says what it
+/// looks like and where it goes.
+///
+// TODO: example
+pub trait LayeredPack {
+    /// What the pack is
+    /// called in messages.
+    const KIND: &'static str;
+    /// The directory that the pack has,
+    /// at its top or one level deep.
+    const ROOT_DIR: &'static str;
+    /// What the files and directories
+    /// that keep track of the packs of
+    /// this kind are prefixed with.
+    const PREFIX: &'static str;
+
+    /// Get the name of the pack.
+    fn get_name(&self) -> &str;
+
+    /// Get the path of the pack, which
+    /// is a directory or an archive.
+    fn get_path(&self) -> &Path;
+
+    /// Get the directory that the packs
+    /// of this kind are installed into.
+    fn get_install_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf;
+
+    /// Get the manifest of the packs of
+    /// this kind of a build of the spec,
+    /// which is kept in the build directory.
+    fn get_manifest<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> BuilderResult<PackManifest> {
+        get_layout::<Self>(spec, repo_dir.as_ref()).load_manifest()
+    }
+
+    /// Get the directory that the files
+    /// which the pack overwrote are
+    /// backed up to.
+    fn get_backup_dir<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> PathBuf {
+        get_layout::<Self>(spec, repo_dir.as_ref()).get_backup_dir(self.get_name())
+    }
+
+    /// Installs the pack.
+    ///
+    /// The pack can be a directory or an
+    /// archive (`.zip`, or tar-based), with
+    /// its root directory at the top, or
+    /// one level deep.
+    ///
+    /// It goes on top of any pack that was
+    /// installed before it, replacing their
+    /// files where they overlap. Every file
+    /// that it overwrites is backed up, so
+    /// that removing the pack restores it.
+    ///
+    /// If the pack is already installed,
+    /// it is removed first.
+    ///
+    // TODO: example
+    fn install<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        self.install_with_progress(spec, repo_dir, |_, _| {})
+    }
+
+    /// Installs the pack, like `install`,
+    /// calling `progress` with the amount of
+    /// installed files and the total amount of
+    /// files after each file.
+    ///
+    // TODO: example
+    fn install_with_progress<P, F>(
+        &self,
+        spec: &Spec,
+        repo_dir: P,
+        progress: F,
+    ) -> BuilderResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(usize, usize),
+    {
+        get_layout::<Self>(spec, repo_dir.as_ref()).install(
+            self.get_name(),
+            self.get_path(),
+            progress,
+        )
+    }
+
+    /// Uninstalls the pack.
+    ///
+    /// Only the files that the pack installed
+    /// are removed, and the files that it
+    /// overwrote are put back. See
+    /// `PackLayout::remove` for the files
+    /// that were changed since.
+    ///
+    // TODO: example
+    fn remove<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        get_layout::<Self>(spec, repo_dir.as_ref()).remove(self.get_name())
+    }
+}
+
+/// Installs packs of one kind on top of each
+/// other, in order, so that later packs
+/// replace the files of earlier ones. The
+/// packs that were installed before are
+/// uninstalled first.
+///
+/// `progress` is called with the name of
+/// the pack, the amount of its files that
+/// are installed, and the total amount of
+/// its files.
+///
+/// Returns the manifest, which tells which
+/// pack each installed file came from.
+pub fn install_layered_packs<'p, T, I, P, F>(
+    spec: &Spec,
+    repo_dir: P,
+    packs: I,
+    mut progress: F,
+) -> BuilderResult<PackManifest>
+where
+    T: LayeredPack + 'p,
+    I: IntoIterator<Item = &'p T>,
+    P: AsRef<Path>,
+    F: FnMut(&str, usize, usize),
+{
+    let repo_dir = repo_dir.as_ref();
+
+    uninstall_layered_packs::<T, _>(spec, repo_dir)?;
+
+    for pack in packs {
+        pack.install_with_progress(spec, repo_dir, |done, total| {
+            progress(pack.get_name(), done, total)
+        })?;
+    }
+
+    T::get_manifest(spec, repo_dir)
+}
+
+/// Uninstalls every pack of one kind that
+/// is installed, newest first, which leaves
+/// the install directory as it was before
+/// them.
+pub fn uninstall_layered_packs<T, P>(spec: &Spec, repo_dir: P) -> BuilderResult<()>
+where
+    T: LayeredPack,
+    P: AsRef<Path>,
+{
+    get_layout::<T>(spec, repo_dir.as_ref()).uninstall_all()
+}
+
+/// Get where a kind of pack
+/// goes in a build.
+pub(crate) fn get_layout<T: LayeredPack + ?Sized>(spec: &Spec, repo_dir: &Path) -> PackLayout {
+    PackLayout {
+        kind: T::KIND,
+        root_dir: T::ROOT_DIR,
+        install_dir: T::get_install_dir(spec, repo_dir),
+        build_dir: get_build_dir(spec, repo_dir),
+        prefix: T::PREFIX,
+        store: spec.pack_store.as_ref().map(PackStore::new),
+    }
+}
+
+/// Where a kind of pack that is layered
+/// over the files of the port goes in a
+/// build, and what it looks like.
+pub(crate) struct PackLayout {
+    /// What the pack is called
+    /// in messages.
+    pub kind: &'static str,
+    /// The directory that a pack has,
+    /// at its top or one level deep.
+    pub root_dir: &'static str,
+    /// Where the root directory
+    /// of a pack is installed.
+    pub install_dir: PathBuf,
+    /// The build directory.
+    pub build_dir: PathBuf,
+    /// What the files and directories
+    /// that keep track of the packs
+    /// are prefixed with.
+    pub prefix: &'static str,
+    /// The store that the files of the
+    /// packs are linked from, if they
+    /// aren't copied.
+    pub store: Option<PackStore>,
+}
+
+/// The root directory of a pack.
+///
+/// If the pack was extracted to the
+/// staging directory, that is removed
+/// when this is dropped, so nothing is
+/// left behind when an install fails.
+pub(crate) struct PackRoot {
+    /// The root directory.
+    pub path: PathBuf,
+    staging: Option<PathBuf>,
+}
+
+impl Drop for PackRoot {
+    fn drop(&mut self) {
+        if let Some(staging_dir) = &self.staging {
+            let _ = fs::remove_dir_all(staging_dir);
+            remove_empty_parent(staging_dir);
+        }
+    }
+}
+
+impl PackLayout {
+    /// Get the path of the manifest.
+    pub fn get_manifest_path(&self) -> PathBuf {
+        self.build_dir.join(format!("{}s.yaml", self.prefix))
+    }
+
+    /// Loads the manifest.
+    pub fn load_manifest(&self) -> BuilderResult<PackManifest> {
+        PackManifest::load(self.get_manifest_path())
+    }
+
+    /// Get the directory that the files
+    /// which a pack overwrote are backed
+    /// up to.
+    pub fn get_backup_dir(&self, name: &str) -> PathBuf {
+        self.build_dir
+            .join(format!("{}_backups", self.prefix))
+            .join(name)
+    }
+
+    /// Get the directory that the files which
+    /// a pack overwrote are kept in, if they
+    /// couldn't be put back when it was
+    /// removed.
+    pub fn get_kept_backup_dir(&self, name: &str) -> PathBuf {
+        self.build_dir
+            .join(format!("{}_kept_backups", self.prefix))
+            .join(name)
+    }
+
+    /// Get the directory that a pack is
+    /// extracted to, if it is an archive.
+    pub fn get_staging_dir(&self, name: &str) -> PathBuf {
+        self.build_dir
+            .join(format!("{}_staging", self.prefix))
+            .join(name)
+    }
+
+    /// Find the root directory of a pack,
+    /// which is a path inside of the archive
+    /// if the pack is one.
+    pub fn find_root_dir(&self, pack_path: &Path) -> BuilderResult<PathBuf> {
+        let is_archive = ArchiveKind::from_path(pack_path).is_some();
+
+        let root = if is_archive {
+            archive::find_root(&archive::list_entries(pack_path)?, self.root_dir)
+        } else {
+            find_root_dir(pack_path, self.root_dir)
+        };
+
+        match root {
+            Some(r) => Ok(r),
+            None => {
+                let inner_err = io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "could not find the {} directory in the {} path!",
+                        self.root_dir, self.kind
+                    ),
+                );
+
+                let err = err!(c_fs!(inner_err), format!("invalid {}", self.kind)); // TODO:
+                Err(err)
+            }
+        }
+    }
+
+    /// Get the root directory of a pack,
+    /// extracting it to the staging
+    /// directory first if the pack is
+    /// an archive.
+    pub fn get_root_dir(&self, name: &str, pack_path: &Path) -> BuilderResult<PackRoot> {
+        let root = self.find_root_dir(pack_path)?;
+
+        if ArchiveKind::from_path(pack_path).is_none() {
+            return Ok(PackRoot {
+                path: root,
+                staging: None,
+            });
+        }
+
+        let staging_dir = self.get_staging_dir(name);
+
+        // made before extracting, so that
+        // a failed extraction is cleaned up.
+        let pack_root = PackRoot {
+            path: staging_dir.join(&root),
+            staging: Some(staging_dir.clone()),
+        };
+
+        remove_dir(&staging_dir)?;
+        archive::extract_dir(pack_path, &root, &staging_dir)?;
+
+        Ok(pack_root)
+    }
+
+    /// Installs a pack on top of any pack that
+    /// was installed before it, backing up every
+    /// file that it overwrites.
+    ///
+    /// If the pack is already installed,
+    /// it is removed first.
+    pub fn install<F>(&self, name: &str, pack_path: &Path, mut progress: F) -> BuilderResult<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let whilst = format!("whilst installing the {}", self.kind);
+        let is_archive = ArchiveKind::from_path(pack_path).is_some();
+
+        // archives are extracted next to where
+        // they are installed, so their files
+        // only have to be moved in.
+        let pack_root = self.get_root_dir(name, pack_path)?;
+        let root_dir = &pack_root.path;
+
+        let files = match util::get_files_recursive(root_dir) {
+            Ok(f) => f,
+            Err(e) => {
+                let msg = format!("failed to read {}", root_dir.display());
+                return Err(err!(c_fs!(e, msg), whilst));
+            }
+        };
+
+        let mut manifest = self.load_manifest()?;
+        if manifest.get_packs().contains(&name) {
+            self.remove(name)?;
+            manifest = self.load_manifest()?;
+        }
+
+        if let Some(store) = &self.store {
+            store.register(self.get_manifest_path())?;
+        }
+
+        let backup_dir = self.get_backup_dir(name);
+        let total_files = files.len();
+
+        for (i, file) in files.into_iter().enumerate() {
+            let source = root_dir.join(&file);
+            let target = self.install_dir.join(&file);
+
+            if let Some(parent) = target.parent() {
+                self.create_dir(parent, &mut manifest)?;
+            }
+
+            let backup = if target.exists() {
+                let backup = backup_dir.join(&file);
+                move_file(&target, &backup)?;
+                Some(backup)
+            } else {
+                None
+            };
+
+            let hash = match &self.store {
+                Some(store) => {
+                    let hash = if is_archive {
+                        store.take(&source)?
+                    } else {
+                        store.add(&source)?
+                    };
+
+                    store.link(&hash, &target)?;
+                    hash
+                }
+                None => self.copy_file(&source, &target, is_archive)?,
+            };
+
+            manifest.record(InstalledFile {
+                path: file,
+                pack: name.to_owned(),
+                hash,
+                backup,
+            });
+
+            // saved as it goes, so that an
+            // interrupted install can still
+            // be removed.
+            manifest.save(self.get_manifest_path())?;
+
+            progress(i + 1, total_files);
+        }
+
+        Ok(())
+    }
+
+    /// Creates a directory that a file of a pack
+    /// goes into, and records the directories
+    /// that it had to create in the manifest.
+    fn create_dir(&self, dir: &Path, manifest: &mut PackManifest) -> BuilderResult<()> {
+        let created_dirs: Vec<PathBuf> = dir
+            .ancestors()
+            .take_while(|ancestor| !ancestor.exists())
+            .filter_map(|ancestor| ancestor.strip_prefix(&self.install_dir).ok())
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .collect();
+
+        if let Err(e) = fs::create_dir_all(dir) {
+            let msg = format!("failed to create {}", dir.display());
+            return Err(err!(
+                c_fs!(e, msg),
+                format!("whilst installing the {}", self.kind)
+            ));
+        }
+
+        manifest.created_dirs.extend(created_dirs);
+
+        Ok(())
+    }
+
+    /// Copies (or moves, if it was extracted
+    /// from an archive) a file of a pack into
+    /// place, and returns its hash.
+    fn copy_file(&self, source: &Path, target: &Path, is_archive: bool) -> BuilderResult<String> {
+        let whilst = format!("whilst installing the {}", self.kind);
+
+        if is_archive {
+            move_file(source, target)?;
+        } else if let Err(e) = fs::copy(source, target) {
+            let msg = format!(
+                "failed to copy the {} from {} to {}",
+                self.kind,
+                source.display(),
+                target.display()
+            );
+            return Err(err!(c_fs!(e, msg), whilst));
+        }
+
+        match util::hash_file(target) {
+            Ok(h) => Ok(h),
+            Err(e) => {
+                let msg = format!("failed to hash {}", target.display());
+                Err(err!(c_fs!(e, msg), whilst))
+            }
+        }
+    }
+
+    /// Uninstalls a pack.
+    ///
+    /// Only the files that the pack installed
+    /// are removed, and the files that it
+    /// overwrote are put back. Files that
+    /// were changed since the pack installed
+    /// them are left alone, and the files
+    /// that they overwrote are kept in the
+    /// kept backups directory instead, and
+    /// listed in the manifest.
+    pub fn remove(&self, name: &str) -> BuilderResult<()> {
+        let whilst = format!("whilst removing the {}", self.kind);
+        let mut manifest = self.load_manifest()?;
+
+        if !manifest.get_packs().contains(&name) {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the {} {} is not installed", self.kind, name),
+            );
+
+            return Err(err!(c_fs!(inner_err), whilst));
+        }
+
+        for i in (0..manifest.files.len()).rev() {
+            if manifest.files[i].pack != name {
+                continue;
+            }
+
+            let file = manifest.files.remove(i);
+
+            // a pack installed later has this file on
+            // top, so what this pack overwrote is now
+            // what that pack overwrote.
+            if let Some(above) = manifest.files[i..].iter_mut().find(|f| f.path == file.path) {
+                if let Some(above_backup) = &above.backup {
+                    remove_file(above_backup)?;
+                }
+
+                above.backup = match file.backup {
+                    Some(backup) => {
+                        let new_backup = self.get_backup_dir(&above.pack).join(&file.path);
+                        move_file(&backup, &new_backup)?;
+                        Some(new_backup)
+                    }
+                    None => None,
+                };
+
+                continue;
+            }
+
+            let target = self.install_dir.join(&file.path);
+            let is_unchanged = util::hash_file(&target).is_ok_and(|hash| hash == file.hash);
+
+            if is_unchanged {
+                remove_file(&target)?;
+
+                if let Some(backup) = &file.backup {
+                    move_file(backup, &target)?;
+                }
+            } else if let Some(backup) = &file.backup {
+                let kept_backup = self.get_kept_backup_dir(name).join(&file.path);
+                move_file(backup, &kept_backup)?;
+
+                manifest.kept_backups.push(KeptBackup {
+                    path: file.path.clone(),
+                    backup: kept_backup,
+                });
+            }
+
+            manifest.save(self.get_manifest_path())?;
+        }
+
+        manifest.save(self.get_manifest_path())?;
+
+        let backup_dir = self.get_backup_dir(name);
+        remove_dir(&backup_dir)?;
+        remove_empty_parent(&backup_dir);
+
+        // the deepest ones first, as their
+        // parents are only empty without them.
+        manifest.created_dirs.sort();
+        manifest.created_dirs.dedup();
+        manifest
+            .created_dirs
+            .sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        manifest.created_dirs.retain(|dir| {
+            let path = self.install_dir.join(dir);
+            // fails if it isn't empty.
+            let _ = fs::remove_dir(&path);
+            path.exists()
+        });
+
+        manifest.save(self.get_manifest_path())
+    }
+
+    /// Uninstalls every pack that is
+    /// installed, newest first, which
+    /// leaves the install directory as
+    /// it was before them.
+    pub fn uninstall_all(&self) -> BuilderResult<()> {
+        let manifest = self.load_manifest()?;
+
+        for name in manifest.get_packs().into_iter().rev() {
+            self.remove(name)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Find a directory in an unpacked pack,
+/// at the top of it or one level deep.
+fn find_root_dir(pack_dir: &Path, dir_name: &str) -> Option<PathBuf> {
+    let root_dir = pack_dir.join(dir_name);
+    if root_dir.is_dir() {
+        return Some(root_dir);
+    }
+
+    let mut nested: Vec<PathBuf> = fs::read_dir(pack_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path().join(dir_name))
+        .filter(|path| path.is_dir())
+        .collect();
+
+    nested.sort();
+    nested.into_iter().next()
+}
+
+/// Moves a file, creating the
+/// directory that it goes into.
+pub(crate) fn move_file(from: &Path, to: &Path) -> BuilderResult<()> {
+    if let Some(parent) = to.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            let msg = format!("failed to create {}", parent.display());
+            return Err(err!(c_fs!(e, msg), "whilst moving a file of a pack"));
+        }
+    }
+
+    if let Err(e) = fs::rename(from, to) {
+        let msg = format!("failed to move {} to {}", from.display(), to.display());
+        return Err(err!(c_fs!(e, msg), "whilst moving a file of a pack"));
+    }
+
+    Ok(())
+}
+
+/// Copies a directory and everything in
+/// it, calling `progress` with the amount
+/// of copied files and the total amount
+/// of files after each file.
+pub(crate) fn copy_dir<F>(from: &Path, to: &Path, mut progress: F) -> BuilderResult<()>
+where
+    F: FnMut(usize, usize),
+{
+    let files = match util::get_files_recursive(from) {
+        Ok(f) => f,
+        Err(e) => {
+            let msg = format!("failed to read {}", from.display());
+            return Err(err!(c_fs!(e, msg), "whilst copying the files of a pack"));
+        }
+    };
+
+    let total_files = files.len();
+
+    for (i, file) in files.into_iter().enumerate() {
+        let source = from.join(&file);
+        let target = to.join(&file);
+
+        if let Some(parent) = target.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                let msg = format!("failed to create {}", parent.display());
+                return Err(err!(c_fs!(e, msg), "whilst copying the files of a pack"));
+            }
+        }
+
+        if let Err(e) = fs::copy(&source, &target) {
+            let msg = format!(
+                "failed to copy {} to {}",
+                source.display(),
+                target.display()
+            );
+            return Err(err!(c_fs!(e, msg), "whilst copying the files of a pack"));
+        }
+
+        progress(i + 1, total_files);
+    }
+
+    Ok(())
+}
+
+/// Removes a directory and everything
+/// in it, if it exists.
+pub(crate) fn remove_dir(path: &Path) -> BuilderResult<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    match fs::remove_dir_all(path) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let msg = format!("failed to remove {}", path.display());
+            Err(err!(c_fs!(e, msg), "whilst cleaning up after a pack"))
+        }
+    }
+}
+
+fn remove_file(path: &Path) -> BuilderResult<()> {
+    match fs::remove_file(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => {
+            let msg = format!("failed to remove {}", path.display());
+            Err(err!(c_fs!(e, msg), "whilst removing a file of a pack"))
+        }
+    }
+}
+
+/// Removes the directory that `path`
+/// is in, if nothing else is in it.
+pub(crate) fn remove_empty_parent(path: &Path) {
+    if let Some(parent) = path.parent() {
+        // fails if it isn't empty.
+        let _ = fs::remove_dir(parent);
+    }
+}
@@ -0,0 +1,35 @@
+/// Reading and extracting the
+/// archives that packs come in.
+pub mod archive;
+
+/// Installing packs that are layered
+/// over the files of the port.
+pub mod layered;
+
+/// Installing sm64ex-coop mods, and
+/// enabling or disabling them.
+pub mod coop;
+
+/// Installing DynOS packs, enabling or
+/// disabling them, and ordering them.
+pub mod dynos;
+
+/// Installing language packs, and
+/// picking the default language.
+pub mod language;
+
+/// Installing model packs.
+pub mod model;
+
+/// A store of the files of packs that
+/// instances share, instead of each
+/// having a copy.
+pub mod store;
+
+/// Installing sound packs, layered
+/// on top of each other.
+pub mod sound;
+
+/// Installing texture packs, layered
+/// on top of each other.
+pub mod texture;
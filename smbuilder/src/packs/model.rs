@@ -0,0 +1,179 @@
+use super::archive::{self, ArchiveKind};
+use super::dynos::{apply_order, load_record, name_taken_err, remove_installed, save_record};
+use super::layered::{remove_dir, remove_empty_parent};
+use crate::builder::artifacts::get_build_dir;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, DynosPack, Error, ModelPack, Spec};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+impl ModelPack {
+    /// Creates a new ModelPack.
+    ///
+    // TODO: example
+    pub fn new<S, P>(name: S, path: P) -> Self
+    where
+        S: ToString,
+        P: Into<PathBuf>,
+    {
+        ModelPack {
+            name: name.to_string(),
+            path: path.into(),
+        }
+    }
+
+    /// Get the directory that the model pack
+    /// is installed into, which is
+    /// `{build_dir}/dynos/packs/{name}`, as
+    /// render96ex loads its models from there.
+    ///
+    /// Model packs are DynOS packs, and can be
+    /// disabled like them, which moves them
+    /// to the directory of the disabled packs.
+    /// Their directories are prefixed with their
+    /// priority too, once they are ordered.
+    pub fn get_install_dir<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> PathBuf {
+        DynosPack::get_packs_dir(spec, repo_dir).join(&self.name)
+    }
+
+    /// Installs the model pack.
+    ///
+    /// The pack can be a directory or an
+    /// archive. It is installed like a DynOS
+    /// pack, so if all that it has is one
+    /// directory (as in `My Models/`), the
+    /// models are taken from in there.
+    ///
+    /// If the pack is already installed,
+    /// it is replaced.
+    ///
+    // TODO: example
+    pub fn install<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        self.install_with_progress(spec, repo_dir, |_, _| {})
+    }
+
+    /// Installs the model pack, like `install`,
+    /// calling `progress` with the amount of
+    /// installed files and the total amount of
+    /// files after each file.
+    ///
+    // TODO: example
+    pub fn install_with_progress<P, F>(
+        &self,
+        spec: &Spec,
+        repo_dir: P,
+        progress: F,
+    ) -> BuilderResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(usize, usize),
+    {
+        let repo_dir = repo_dir.as_ref();
+
+        if ArchiveKind::from_path(&self.path).is_none() {
+            return DynosPack::new(&self.name, &self.path)
+                .install_as(spec, repo_dir, &self.name, progress);
+        }
+
+        let staging_dir = get_build_dir(spec, repo_dir)
+            .join("model_pack_staging")
+            .join(&self.name);
+
+        remove_dir(&staging_dir)?;
+
+        let result = archive::extract_all(&self.path, &staging_dir).and_then(|_| {
+            DynosPack::new(&self.name, &staging_dir)
+                .install_as(spec, repo_dir, &self.name, progress)
+        });
+
+        // the staging directory goes,
+        // whether it installed or not.
+        remove_dir(&staging_dir)?;
+        remove_empty_parent(&staging_dir);
+
+        result
+    }
+
+    /// Uninstalls the model pack,
+    /// whether it is enabled or not.
+    ///
+    /// Only the pack's own directory
+    /// is removed.
+    ///
+    // TODO: example
+    pub fn remove<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+        if !remove_installed(spec, repo_dir.as_ref(), &self.name)? {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the model pack {} is not installed", self.name),
+            );
+            return Err(err!(c_fs!(inner_err), "whilst removing the model pack"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Installs model packs, replacing the ones
+/// that were installed by an earlier call.
+///
+/// `progress` is called with the name of
+/// the pack, the amount of its files that
+/// are installed, and the total amount of
+/// its files.
+pub fn install_model_packs<P, F>(
+    spec: &Spec,
+    repo_dir: P,
+    packs: &[ModelPack],
+    mut progress: F,
+) -> BuilderResult<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, usize, usize),
+{
+    let repo_dir = repo_dir.as_ref();
+
+    uninstall_model_packs(spec, repo_dir)?;
+
+    for pack in packs {
+        // they would replace each other.
+        if load_record(spec, repo_dir)?.installed.contains(&pack.name) {
+            return Err(name_taken_err(&pack.name, "a DynOS pack"));
+        }
+
+        pack.install_with_progress(spec, repo_dir, |done, total| {
+            progress(&pack.name, done, total)
+        })?;
+
+        // they are kept with the DynOS packs
+        // of the build, and loaded again, as
+        // installing the pack renames it.
+        let mut record = load_record(spec, repo_dir)?;
+        record.models.push(pack.name.clone());
+        save_record(spec, repo_dir, &record)?;
+    }
+
+    apply_order(spec, repo_dir)
+}
+
+/// Uninstalls the model packs that were
+/// installed by `install_model_packs`,
+/// enabled or not.
+pub fn uninstall_model_packs<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> BuilderResult<()> {
+    let repo_dir = repo_dir.as_ref();
+
+    for name in load_record(spec, repo_dir)?.models {
+        // it may have been removed by hand.
+        remove_installed(spec, repo_dir, &name)?;
+    }
+
+    // loaded again, as removing the
+    // packs forgets their renames.
+    let mut record = load_record(spec, repo_dir)?;
+    record.models.clear();
+
+    save_record(spec, repo_dir, &record)
+}
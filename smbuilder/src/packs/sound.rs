@@ -0,0 +1,42 @@
+use super::layered::LayeredPack;
+use crate::builder::artifacts::get_build_dir;
+use crate::prelude::{SoundPack, Spec};
+
+use std::path::{Path, PathBuf};
+
+impl SoundPack {
+    /// Creates a new SoundPack.
+    ///
+    // TODO: example
+    pub fn new<S, P>(name: S, path: P) -> Self
+    where
+        S: ToString,
+        P: Into<PathBuf>,
+    {
+        SoundPack {
+            name: name.to_string(),
+            path: path.into(),
+        }
+    }
+}
+
+impl LayeredPack for SoundPack {
+    const KIND: &'static str = "sound pack";
+    const ROOT_DIR: &'static str = "sound";
+    const PREFIX: &'static str = "sound_pack";
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the directory that sound
+    /// packs are installed into, which
+    /// is `{build_dir}/res/sound`.
+    fn get_install_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir).join("res").join("sound")
+    }
+}
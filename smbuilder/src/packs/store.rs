@@ -0,0 +1,617 @@
+use super::layered::PackManifest;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error};
+use crate::util;
+
+use duct::cmd;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Keeps the temporary files of
+/// builds that add to the same store
+/// at once apart.
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How long a file has to be left alone
+/// for, before a garbage collection counts
+/// it as left behind: a temporary file by a
+/// build that was stopped, or a file that no
+/// manifest lists by a build that was still
+/// installing it.
+const STALE_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A lock on a store, which is
+/// released when it is dropped.
+struct StoreLock {
+    _file: fs::File,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// How a file of the store was
+/// put into a build, best first.
+pub enum LinkKind {
+    /// A copy that shares its data with
+    /// the store until either of them is
+    /// changed, made with `cp --reflink`.
+    Reflink,
+    /// A hard link, which is the same
+    /// file as the one in the store.
+    Hardlink,
+    /// A plain copy, for when the build
+    /// and the store can't share files
+    /// (as in on different disks).
+    Copy,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// What a garbage collection
+/// of a store removed.
+pub struct StoreGcReport {
+    /// The amount of files
+    /// that were removed.
+    pub removed: usize,
+    /// The amount of bytes
+    /// that were freed.
+    pub freed_bytes: u64,
+}
+
+impl fmt::Display for StoreGcReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "removed {} file(s), freeing {:.1} MiB",
+            self.removed,
+            self.freed_bytes as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A directory that the files of packs are
+/// kept in, named after their hashes, so that
+/// instances which install the same packs
+/// share them instead of each having a copy.
+///
+/// Files are put into builds as reflinks if
+/// the filesystem supports them, and hard
+/// links if not. The files in the store are
+/// read-only, as a hard link that is changed
+/// changes the store too.
+///
+// TODO: example
+pub struct PackStore {
+    /// The directory of the store.
+    pub root: PathBuf,
+    /// The best way of linking that has
+    /// worked so far, as trying the ones
+    /// that don't for every file is slow.
+    best_link: Cell<LinkKind>,
+}
+
+impl PackStore {
+    /// Creates a new PackStore,
+    /// in a directory that may
+    /// not exist yet.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        PackStore {
+            root: root.into(),
+            best_link: Cell::new(LinkKind::Reflink),
+        }
+    }
+
+    /// Get the path of the file
+    /// with a hash in the store.
+    pub fn get_object_path(&self, hash: &str) -> PathBuf {
+        let prefix = hash.get(..2).unwrap_or(hash);
+        self.root.join("objects").join(prefix).join(hash)
+    }
+
+    /// Get the path of the list of
+    /// the manifests that use the store.
+    pub fn get_manifests_path(&self) -> PathBuf {
+        self.root.join("manifests.yaml")
+    }
+
+    /// Get the path of the file that
+    /// is locked whilst the list of the
+    /// manifests is changed.
+    pub fn get_lock_path(&self) -> PathBuf {
+        self.root.join("lock")
+    }
+
+    /// Checks if a file with a
+    /// hash is in the store.
+    pub fn contains(&self, hash: &str) -> bool {
+        self.get_object_path(hash).exists()
+    }
+
+    /// Adds a file to the store, if it
+    /// isn't already in it, and returns
+    /// its hash.
+    pub fn add<P: AsRef<Path>>(&self, file: P) -> BuilderResult<String> {
+        self.put(file.as_ref(), false)
+    }
+
+    /// Adds a file to the store like `add`,
+    /// but moves it in instead of copying,
+    /// which is for files that were only
+    /// extracted to be installed.
+    pub(crate) fn take(&self, file: &Path) -> BuilderResult<String> {
+        self.put(file, true)
+    }
+
+    /// Puts a file of the store into
+    /// a build, replacing what is there.
+    ///
+    /// Returns how it was put there.
+    pub fn link<P: AsRef<Path>>(&self, hash: &str, target: P) -> BuilderResult<LinkKind> {
+        let target = target.as_ref();
+        let object = self.get_object_path(hash);
+
+        if !object.exists() {
+            let inner_err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the pack store has no file with the hash {}", hash),
+            );
+            return Err(err!(
+                c_fs!(inner_err),
+                "whilst linking a file of the pack store"
+            ));
+        }
+
+        if let Some(parent) = target.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                let msg = format!("failed to create {}", parent.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst linking a file of the pack store"
+                ));
+            }
+        }
+
+        if target.exists() {
+            if let Err(e) = fs::remove_file(target) {
+                let msg = format!("failed to remove {}", target.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst linking a file of the pack store"
+                ));
+            }
+        }
+
+        if self.best_link.get() <= LinkKind::Reflink {
+            if reflink(&object, target) {
+                return Ok(LinkKind::Reflink);
+            }
+
+            // the filesystem doesn't support them,
+            // so the rest of the files don't try.
+            self.best_link.set(LinkKind::Hardlink);
+        }
+
+        if self.best_link.get() <= LinkKind::Hardlink && fs::hard_link(&object, target).is_ok() {
+            self.best_link.set(LinkKind::Hardlink);
+            return Ok(LinkKind::Hardlink);
+        }
+
+        self.best_link.set(LinkKind::Copy);
+
+        // copied without the permissions,
+        // so that the copy is writable.
+        match copy_contents(&object, target) {
+            Ok(_) => Ok(LinkKind::Copy),
+            Err(e) => {
+                let msg = format!(
+                    "failed to copy {} to {}",
+                    object.display(),
+                    target.display()
+                );
+                Err(err!(
+                    c_fs!(e, msg),
+                    "whilst linking a file of the pack store"
+                ))
+            }
+        }
+    }
+
+    /// Adds a manifest to the ones that
+    /// use the store, so that the files
+    /// it lists are kept.
+    ///
+    /// The store is locked whilst it is
+    /// added, so builds that share it
+    /// don't lose each other's manifests.
+    pub fn register<P: AsRef<Path>>(&self, manifest_path: P) -> BuilderResult<()> {
+        let manifest_path = manifest_path.as_ref();
+
+        // relative paths only work from where
+        // smbuilder was run, so they are
+        // made absolute.
+        let manifest_path = match manifest_path.parent().map(fs::canonicalize) {
+            Some(Ok(parent)) => parent.join(manifest_path.file_name().unwrap_or_default()),
+            _ => manifest_path.to_path_buf(),
+        };
+
+        let _lock = self.lock()?;
+        let mut manifests = self.load_manifests()?;
+
+        if manifests.contains(&manifest_path) {
+            return Ok(());
+        }
+
+        manifests.push(manifest_path);
+        self.save_manifests(&manifests)
+    }
+
+    /// Get the hashes of the files that
+    /// the manifests which use the store
+    /// list.
+    pub fn get_referenced(&self) -> BuilderResult<HashSet<String>> {
+        let mut referenced = HashSet::new();
+
+        for manifest_path in self.load_manifests()? {
+            if !manifest_path.exists() {
+                continue;
+            }
+
+            let manifest = PackManifest::load(&manifest_path)?;
+            referenced.extend(manifest.files.into_iter().map(|file| file.hash));
+        }
+
+        Ok(referenced)
+    }
+
+    /// Removes the files that no manifest
+    /// lists, and forgets the manifests
+    /// that don't exist anymore.
+    ///
+    /// Files that are still hard linked into
+    /// a build are kept, as removing them
+    /// wouldn't free anything. So are the
+    /// files that builds may still be adding
+    /// to the store, and installing before
+    /// their manifests list them, until they
+    /// are an hour old.
+    ///
+    // TODO: example
+    pub fn gc(&self) -> BuilderResult<StoreGcReport> {
+        if !self.root.exists() {
+            return Ok(StoreGcReport::default());
+        }
+
+        let _lock = self.lock()?;
+
+        let referenced = self.get_referenced()?;
+        let objects_dir = self.root.join("objects");
+        let mut report = StoreGcReport::default();
+
+        let objects = match util::get_files_recursive(&objects_dir) {
+            Ok(o) => o,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                let msg = format!("failed to read {}", objects_dir.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst collecting the garbage of the pack store"
+                ));
+            }
+        };
+
+        for object in objects {
+            let hash = object.file_name().unwrap_or_default().to_string_lossy();
+
+            if referenced.contains(hash.as_ref()) {
+                continue;
+            }
+
+            let path = objects_dir.join(&object);
+            let metadata = match fs::metadata(&path) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+
+            // a build still has it, or
+            // may be installing it.
+            if metadata.nlink() > 1 || get_age(&metadata) < STALE_FILE_AGE {
+                continue;
+            }
+
+            if let Err(e) = fs::remove_file(&path) {
+                let msg = format!("failed to remove {}", path.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst collecting the garbage of the pack store"
+                ));
+            }
+
+            if let Some(parent) = path.parent() {
+                // fails if it isn't empty.
+                let _ = fs::remove_dir(parent);
+            }
+
+            report.removed += 1;
+            report.freed_bytes += metadata.len();
+        }
+
+        self.remove_stale_temp_files(&mut report)?;
+
+        let mut manifests = self.load_manifests()?;
+        manifests.retain(|path| path.exists());
+        self.save_manifests(&manifests)?;
+
+        Ok(report)
+    }
+
+    /// Locks the store, waiting for
+    /// any other build that has it
+    /// locked to be done with it.
+    fn lock(&self) -> BuilderResult<StoreLock> {
+        if let Err(e) = fs::create_dir_all(&self.root) {
+            let msg = format!("failed to create {}", self.root.display());
+            return Err(err!(c_fs!(e, msg), "whilst locking the pack store"));
+        }
+
+        let path = self.get_lock_path();
+        let file = match fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+        {
+            Ok(f) => f,
+            Err(e) => {
+                let msg = format!("failed to open {}", path.display());
+                return Err(err!(c_fs!(e, msg), "whilst locking the pack store"));
+            }
+        };
+
+        // SAFETY: the file is open for as
+        // long as the lock is held.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            let msg = format!("failed to lock {}", path.display());
+            return Err(err!(
+                c_fs!(io::Error::last_os_error(), msg),
+                "whilst locking the pack store"
+            ));
+        }
+
+        Ok(StoreLock { _file: file })
+    }
+
+    /// Removes the temporary files that
+    /// builds which were stopped whilst
+    /// adding to the store left behind.
+    fn remove_stale_temp_files(&self, report: &mut StoreGcReport) -> BuilderResult<()> {
+        let temp_dir = self.root.join("tmp");
+
+        let entries = match fs::read_dir(&temp_dir) {
+            Ok(e) => e,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                let msg = format!("failed to read {}", temp_dir.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst collecting the garbage of the pack store"
+                ));
+            }
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let metadata = match fs::metadata(&path) {
+                Ok(m) if m.is_file() => m,
+                _ => continue,
+            };
+
+            if get_age(&metadata) < STALE_FILE_AGE {
+                continue;
+            }
+
+            if let Err(e) = fs::remove_file(&path) {
+                let msg = format!("failed to remove {}", path.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst collecting the garbage of the pack store"
+                ));
+            }
+
+            report.removed += 1;
+            report.freed_bytes += metadata.len();
+        }
+
+        Ok(())
+    }
+
+    fn put(&self, file: &Path, move_in: bool) -> BuilderResult<String> {
+        let hash = match util::hash_file(file) {
+            Ok(h) => h,
+            Err(e) => {
+                let msg = format!("failed to hash {}", file.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst adding a file to the pack store"
+                ));
+            }
+        };
+
+        let object = self.get_object_path(&hash);
+
+        if object.exists() {
+            if move_in {
+                let _ = fs::remove_file(file);
+            }
+            return Ok(hash);
+        }
+
+        let temp_dir = self.root.join("tmp");
+        if let Err(e) = fs::create_dir_all(&temp_dir) {
+            let msg = format!("failed to create {}", temp_dir.display());
+            return Err(err!(
+                c_fs!(e, msg),
+                "whilst adding a file to the pack store"
+            ));
+        }
+
+        // written next to the store first, so
+        // that a file with a hash is never
+        // half of one.
+        let temp_file = temp_dir.join(format!(
+            "{}.{}.{}",
+            hash,
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // a rename fails if the file is
+        // on another disk, so it is
+        // copied instead.
+        let is_moved = move_in && fs::rename(file, &temp_file).is_ok();
+        if !is_moved {
+            if let Err(e) = copy_contents(file, &temp_file) {
+                let msg = format!(
+                    "failed to copy {} to {}",
+                    file.display(),
+                    temp_file.display()
+                );
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst adding a file to the pack store"
+                ));
+            }
+        }
+
+        if let Ok(metadata) = fs::metadata(&temp_file) {
+            let mut permissions = metadata.permissions();
+            permissions.set_readonly(true);
+            let _ = fs::set_permissions(&temp_file, permissions);
+        }
+
+        if let Some(parent) = object.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                let msg = format!("failed to create {}", parent.display());
+                return Err(err!(
+                    c_fs!(e, msg),
+                    "whilst adding a file to the pack store"
+                ));
+            }
+        }
+
+        if let Err(e) = fs::rename(&temp_file, &object) {
+            let _ = fs::remove_file(&temp_file);
+            let msg = format!(
+                "failed to move {} to {}",
+                temp_file.display(),
+                object.display()
+            );
+            return Err(err!(
+                c_fs!(e, msg),
+                "whilst adding a file to the pack store"
+            ));
+        }
+
+        if move_in && !is_moved {
+            let _ = fs::remove_file(file);
+        }
+
+        Ok(hash)
+    }
+
+    fn load_manifests(&self) -> BuilderResult<Vec<PathBuf>> {
+        let path = self.get_manifests_path();
+
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file_string = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("failed to read {}", path.display());
+                return Err(err!(c_fs!(e, msg), "whilst loading the pack store"));
+            }
+        };
+
+        match serde_yaml::from_str(&file_string) {
+            Ok(manifests) => Ok(manifests),
+            Err(e) => Err(err!(
+                c_other!(e),
+                "failed to parse the manifests of the pack store"
+            )),
+        }
+    }
+
+    fn save_manifests(&self, manifests: &[PathBuf]) -> BuilderResult<()> {
+        let path = self.get_manifests_path();
+
+        if let Err(e) = fs::create_dir_all(&self.root) {
+            let msg = format!("failed to create {}", self.root.display());
+            return Err(err!(c_fs!(e, msg), "whilst saving the pack store"));
+        }
+
+        let contents = match serde_yaml::to_string(manifests) {
+            Ok(s) => s,
+            Err(e) => {
+                return Err(err!(
+                    c_other!(e),
+                    "failed to serialize the manifests of the pack store"
+                ))
+            }
+        };
+
+        match fs::write(&path, contents) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let msg = format!("failed to write to {}", path.display());
+                Err(err!(c_fs!(e, msg), "whilst saving the pack store"))
+            }
+        }
+    }
+}
+
+/// Get how long ago a file of the store was
+/// last changed, counting being moved or
+/// linked, unlike its modification time,
+/// which a file that was moved in keeps.
+fn get_age(metadata: &fs::Metadata) -> Duration {
+    let changed = SystemTime::UNIX_EPOCH + Duration::from_secs(metadata.ctime().max(0) as u64);
+
+    SystemTime::now()
+        .duration_since(changed)
+        .unwrap_or_default()
+}
+
+/// Makes a reflink of a file, which
+/// only works on filesystems that
+/// support them (as in Btrfs and XFS).
+fn reflink(from: &Path, to: &Path) -> bool {
+    let linked = cmd!("cp", "--reflink=always", "--no-preserve=mode", from, to)
+        .stdout_null()
+        .stderr_null()
+        .run()
+        .is_ok();
+
+    if !linked {
+        // cp may leave an empty file behind.
+        let _ = fs::remove_file(to);
+    }
+
+    linked
+}
+
+/// Copies the contents of a file, but not
+/// its permissions, unlike `fs::copy`.
+fn copy_contents(from: &Path, to: &Path) -> io::Result<u64> {
+    let mut source = fs::File::open(from)?;
+    let mut target = fs::File::create(to)?;
+
+    io::copy(&mut source, &mut target)
+}
@@ -0,0 +1,358 @@
+use super::archive::{self, ArchiveKind};
+use super::layered::{self, LayeredPack};
+use crate::builder::artifacts::get_build_dir;
+use crate::builder::types::BuilderResult;
+use crate::error::ErrorCause;
+use crate::prelude::error_macros::*;
+use crate::prelude::{err, Error, Spec, TexturePack};
+use crate::util;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A texture that is not the texture
+/// that it replaces, scaled up by a
+/// power of two.
+pub struct TextureDimensionMismatch {
+    /// The path of the texture,
+    /// relative to `res/gfx`.
+    pub path: PathBuf,
+    /// The width and height of
+    /// the texture it replaces.
+    pub expected: (u32, u32),
+    /// The width and height
+    /// of the texture.
+    pub found: (u32, u32),
+}
+
+#[derive(Clone, Debug, Default)]
+/// The result of checking a texture pack
+/// against the textures of the port.
+///
+// TODO: example
+pub struct TexturePackReport {
+    /// The name of the pack.
+    pub name: String,
+    /// Textures that the port
+    /// doesn't have.
+    pub unknown_files: Vec<PathBuf>,
+    /// Files that aren't PNG images.
+    pub wrong_format: Vec<PathBuf>,
+    /// Textures that don't fit the
+    /// texture they replace.
+    pub wrong_dimensions: Vec<TextureDimensionMismatch>,
+    /// The textures of the port
+    /// that the pack replaces.
+    pub replaced: Vec<PathBuf>,
+    /// The amount of textures
+    /// that the port has.
+    pub vanilla_count: usize,
+}
+
+impl TexturePackReport {
+    /// Get the percentage of the port's
+    /// textures that the pack replaces.
+    pub fn get_coverage(&self) -> f64 {
+        if self.vanilla_count == 0 {
+            return 0.0;
+        }
+
+        (self.replaced.len() as f64 / self.vanilla_count as f64) * 100.0
+    }
+
+    /// Checks if the pack has no broken
+    /// textures. Unknown files are only
+    /// unused, so they don't count.
+    pub fn is_valid(&self) -> bool {
+        self.wrong_format.is_empty() && self.wrong_dimensions.is_empty()
+    }
+}
+
+impl fmt::Display for TexturePackReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for path in &self.wrong_format {
+            writeln!(f, "not a PNG image: {}", path.display())?;
+        }
+
+        for mismatch in &self.wrong_dimensions {
+            writeln!(
+                f,
+                "wrong dimensions: {} is {}x{}, but replaces a {}x{} texture",
+                mismatch.path.display(),
+                mismatch.found.0,
+                mismatch.found.1,
+                mismatch.expected.0,
+                mismatch.expected.1
+            )?;
+        }
+
+        for path in &self.unknown_files {
+            writeln!(f, "unknown texture: {}", path.display())?;
+        }
+
+        write!(
+            f,
+            "{} replaces {} of {} textures ({:.1}%)",
+            self.name,
+            self.replaced.len(),
+            self.vanilla_count,
+            self.get_coverage()
+        )
+    }
+}
+
+impl TexturePack {
+    /// Creates a new TexturePack.
+    ///
+    // TODO: example
+    pub fn new<S, P>(name: S, path: P) -> Self
+    where
+        S: ToString,
+        P: Into<PathBuf>,
+    {
+        TexturePack {
+            name: name.to_string(),
+            path: path.into(),
+        }
+    }
+
+    /// Checks the texture pack against the
+    /// textures that the port ships with.
+    ///
+    /// Textures may be scaled up, but only
+    /// by a power of two (2x, 4x, etc.),
+    /// the same along both sides.
+    ///
+    /// The port's textures have to have been
+    /// extracted, so the repository has to
+    /// be set up first.
+    ///
+    // TODO: example
+    pub fn inspect<P: AsRef<Path>>(
+        &self,
+        spec: &Spec,
+        repo_dir: P,
+    ) -> BuilderResult<TexturePackReport> {
+        let repo_dir = repo_dir.as_ref();
+        let vanilla_textures = get_vanilla_textures(spec, repo_dir)?;
+
+        let layout = layered::get_layout::<TexturePack>(spec, repo_dir);
+        let root = layout.find_root_dir(&self.path)?;
+
+        // only the headers of the textures are
+        // needed, so archives aren't extracted.
+        let textures = if ArchiveKind::from_path(&self.path).is_some() {
+            get_archived_textures(&self.path, &root)?
+        } else {
+            match util::get_files_recursive(&root) {
+                Ok(files) => files
+                    .into_iter()
+                    .map(|file| {
+                        let dimensions = get_png_dimensions(root.join(&file));
+                        (file, dimensions)
+                    })
+                    .collect(),
+                Err(e) => {
+                    let msg = format!("failed to read {}", root.display());
+                    return Err(err!(c_fs!(e, msg), "whilst inspecting the texture pack"));
+                }
+            }
+        };
+
+        let mut report = TexturePackReport {
+            name: self.name.clone(),
+            vanilla_count: vanilla_textures.len(),
+            ..Default::default()
+        };
+
+        for (file, dimensions) in textures {
+            let is_png = file
+                .extension()
+                .map(|e| e.eq_ignore_ascii_case("png"))
+                .unwrap_or(false);
+
+            let found = match dimensions {
+                Some(d) if is_png => d,
+                _ => {
+                    report.wrong_format.push(file);
+                    continue;
+                }
+            };
+
+            if !vanilla_textures.contains(&file) {
+                report.unknown_files.push(file);
+                continue;
+            }
+
+            // only the repository has the
+            // textures themselves.
+            if let Some(expected) = get_png_dimensions(repo_dir.join(&file)) {
+                if !is_scaled_texture(found, expected) {
+                    report.wrong_dimensions.push(TextureDimensionMismatch {
+                        path: file.clone(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+
+            report.replaced.push(file);
+        }
+
+        Ok(report)
+    }
+}
+
+impl LayeredPack for TexturePack {
+    const KIND: &'static str = "texture pack";
+    const ROOT_DIR: &'static str = "gfx";
+    const PREFIX: &'static str = "texture_pack";
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the directory that texture
+    /// packs are installed into, which
+    /// is `{build_dir}/res/gfx`.
+    fn get_install_dir<P: AsRef<Path>>(spec: &Spec, repo_dir: P) -> PathBuf {
+        get_build_dir(spec, repo_dir).join("res").join("gfx")
+    }
+}
+
+/// Get the textures that the port ships with,
+/// relative to `res/gfx`.
+///
+/// They are read from the `res/base.zip` of the
+/// build if there is one, and from the assets
+/// extracted to the repository otherwise.
+pub fn get_vanilla_textures<P: AsRef<Path>>(
+    spec: &Spec,
+    repo_dir: P,
+) -> BuilderResult<HashSet<PathBuf>> {
+    let repo_dir = repo_dir.as_ref();
+    let base_zip = get_build_dir(spec, repo_dir).join("res").join("base.zip");
+
+    let mut textures: HashSet<PathBuf> = if base_zip.exists() {
+        archive::list_entries(&base_zip)?
+            .into_iter()
+            .filter_map(|entry| entry.strip_prefix("gfx").ok().map(Path::to_path_buf))
+            .collect()
+    } else {
+        let mut textures = HashSet::new();
+
+        for dir in ["actors", "levels", "textures"] {
+            if let Ok(files) = util::get_files_recursive(repo_dir.join(dir)) {
+                textures.extend(files.into_iter().map(|f| Path::new(dir).join(f)));
+            }
+        }
+
+        textures
+    };
+
+    textures.retain(|t| t.extension().map(|e| e == "png").unwrap_or(false));
+
+    if textures.is_empty() {
+        let inner_err = io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not find the textures of the port, have the assets been extracted?",
+        );
+        return Err(err!(
+            c_fs!(inner_err),
+            "whilst reading the textures of the port"
+        ));
+    }
+
+    Ok(textures)
+}
+
+/// A file of a texture pack, and the width
+/// and height of it if it's a PNG image.
+type PackTexture = (PathBuf, Option<(u32, u32)>);
+
+/// Get the files under the root directory
+/// of an archived texture pack, relative to
+/// it, along with the width and height of
+/// the ones that are PNG images.
+fn get_archived_textures(archive: &Path, root: &Path) -> BuilderResult<Vec<PackTexture>> {
+    // directories end with a slash.
+    let files: Vec<PathBuf> = archive::list_entries(archive)?
+        .into_iter()
+        .filter(|entry| entry.starts_with(root) && !entry.to_string_lossy().ends_with('/'))
+        .collect();
+
+    let heads = archive::read_file_heads(archive, &files, PNG_HEADER_LEN)?;
+
+    let textures = files
+        .into_iter()
+        .map(|entry| {
+            let dimensions = heads.get(&entry).and_then(|h| parse_png_dimensions(h));
+            let file = entry.strip_prefix(root).unwrap_or(&entry).to_path_buf();
+            (file, dimensions)
+        })
+        .collect();
+
+    Ok(textures)
+}
+
+/// Checks if a texture is another one
+/// scaled up by a power of two, which
+/// is what the game can draw in its
+/// place.
+fn is_scaled_texture(found: (u32, u32), expected: (u32, u32)) -> bool {
+    let (found_w, found_h) = found;
+    let (expected_w, expected_h) = expected;
+
+    if expected_w == 0 || expected_h == 0 {
+        return found == expected;
+    }
+
+    if found_w % expected_w != 0 || found_h % expected_h != 0 {
+        return false;
+    }
+
+    let scale = found_w / expected_w;
+    scale.is_power_of_two() && found_h / expected_h == scale
+}
+
+/// The length of the part of a PNG image
+/// that holds its width and height.
+const PNG_HEADER_LEN: usize = 24;
+
+/// Get the width and height of a PNG image
+/// from its header, or `None` if it isn't
+/// one.
+fn get_png_dimensions<P: AsRef<Path>>(path: P) -> Option<(u32, u32)> {
+    let mut header = [0u8; PNG_HEADER_LEN];
+    fs::File::open(path).ok()?.read_exact(&mut header).ok()?;
+
+    parse_png_dimensions(&header)
+}
+
+/// Get the width and height of a PNG image
+/// from the start of it, or `None` if it
+/// isn't one.
+fn parse_png_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+    if header.len() < PNG_HEADER_LEN {
+        return None;
+    }
+
+    if header[..8] != PNG_SIGNATURE || &header[12..16] != b"IHDR" {
+        return None;
+    }
+
+    let width = u32::from_be_bytes(header[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(header[20..24].try_into().ok()?);
+
+    Some((width, height))
+}
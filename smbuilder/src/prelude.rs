@@ -4,13 +4,51 @@ pub mod romconvert {
 }
 
 // Builder stuff
+pub use crate::builder::artifacts::{
+    collect_windows_dlls, find_executable, get_build_dir, get_dist_dir, get_launch_args,
+};
 pub use crate::builder::builder::Builder;
+pub use crate::builder::cancel::CancellationToken;
+pub use crate::builder::ccache::CcacheStats;
+pub use crate::builder::fingerprint::BuildFingerprint;
+pub use crate::builder::history::{BuildRecord, StageTiming};
+pub use crate::builder::queue::{BuildQueue, BuildReport, QueuedBuildReport};
+pub use crate::builder::state::BuildState;
 pub use crate::builder::types as builder_types;
+pub use crate::builder::types::PlanStep;
 
 // callbacks
 pub use crate::callbacks::types as callback_types;
 pub use crate::callbacks::*;
 
+// diagnostics
+pub use crate::diagnostics::hints::{Hint, HintRule, HintRules};
+pub use crate::diagnostics::types as diagnostic_types;
+pub use crate::diagnostics::{get_compiler_output_file, get_failed_make_target, DiagnosticParser};
+
+// packs
+pub use crate::packs::coop::{
+    install_coop_mods, list_coop_mods, set_coop_mod_enabled, uninstall_coop_mods, CoopModMetadata,
+    InstalledCoopMod,
+};
+pub use crate::packs::dynos::{
+    install_dynos_packs, list_dynos_packs, set_dynos_pack_enabled, set_dynos_pack_order,
+    uninstall_dynos_packs, DynosPackReport, InstalledDynosPack,
+};
+pub use crate::packs::language::{get_languages, set_default_language};
+pub use crate::packs::layered::{
+    install_layered_packs, uninstall_layered_packs, InstalledFile, KeptBackup, LayeredPack,
+    PackManifest,
+};
+pub use crate::packs::model::{install_model_packs, uninstall_model_packs};
+pub use crate::packs::store::{LinkKind, PackStore, StoreGcReport};
+pub use crate::packs::texture::{
+    get_vanilla_textures, TextureDimensionMismatch, TexturePackReport,
+};
+
+// toolchain
+pub use crate::toolchain::types as toolchain_types;
+
 // spec
 pub use crate::spec::*;
 
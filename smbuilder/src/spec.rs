@@ -8,7 +8,7 @@ use crate::util;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Default, Builder, Deserialize, Serialize)]
 /// Represents a build spec.
@@ -32,6 +32,13 @@ pub struct Spec {
     /// Make flags to be passed to the
     /// compiler.
     pub makeopts: Option<Vec<Makeopt>>,
+    /// Overrides for the compiler, and
+    /// whether to use `ccache`.
+    pub compiler: Option<CompilerConfig>,
+    /// The platform to build for. Builds
+    /// for the platform smbuilder runs
+    /// on if it is not set.
+    pub target: Option<Target>,
     /// DynOS packs, if supported.
     pub dynos_packs: Option<Vec<DynosPack>>,
     /// Patrhes.
@@ -39,7 +46,35 @@ pub struct Spec {
     /// Post install scripts.
     pub scripts: Option<Vec<PostBuildScript>>,
     /// A texture pack.
+    ///
+    /// It is installed before the ones in
+    /// `texture_packs`, kept for older specs.
     pub texture_pack: Option<TexturePack>,
+    /// Texture packs, installed in order.
+    /// Later packs replace the files of
+    /// earlier ones.
+    pub texture_packs: Option<Vec<TexturePack>>,
+    /// Sound packs, installed in order,
+    /// like texture packs.
+    pub sound_packs: Option<Vec<SoundPack>>,
+    /// Language packs, installed
+    /// in order.
+    pub language_packs: Option<Vec<LanguagePack>>,
+    /// The language that the game
+    /// starts in, named after its
+    /// file in `res/texts`.
+    pub default_language: Option<String>,
+    /// Model packs, if DynOS
+    /// is supported.
+    pub model_packs: Option<Vec<ModelPack>>,
+    /// Lua mods, for sm64ex-coop.
+    pub coop_mods: Option<Vec<CoopMod>>,
+    /// A pack store, which can be shared
+    /// between instances. The files of
+    /// texture, sound and language packs
+    /// are linked from it instead of
+    /// copied if it is set.
+    pub pack_store: Option<PathBuf>,
 }
 
 impl Spec {
@@ -140,11 +175,12 @@ impl Spec {
         }
     }
 
-    /// Gets a build shell script, ready to be
-    /// written to disk.
+    /// Gets the `make` command that
+    /// builds the spec, with all of its
+    /// make flags.
     ///
     //  TODO: example
-    pub fn to_script(&self, repo_path: &Path) -> String {
+    pub fn get_make_cmd(&self, repo_path: &Path) -> String {
         let makeopts_string = if let Some(makeopts) = &self.makeopts {
             util::get_makeopts_string(makeopts)
         } else {
@@ -159,17 +195,82 @@ impl Spec {
         #[cfg(target_os = "linux")]
         let make_cmd = "make";
 
-        let platform_makeopts = util::get_makeopts_string(&Makeopt::default_makeopts());
+        let platform_makeopts =
+            util::get_makeopts_string(&Makeopt::default_makeopts_for(self.get_target()));
 
         let jobs = self.jobs.unwrap_or(2);
 
-        let full_repo_dir = fs::canonicalize(repo_path).unwrap_or_else(|e| {
-            panic!(
-                "failed to get the absolute path from {}: {}",
-                &repo_path.display(),
-                e
-            )
-        });
+        format!(
+            "{} -C {} {} {} -j{}",
+            make_cmd,
+            util::shell_quote(&get_full_repo_dir(repo_path).to_string_lossy()),
+            platform_makeopts,
+            makeopts_string,
+            jobs
+        )
+    }
+
+    /// Get every texture pack of the
+    /// spec, in the order that they
+    /// are installed in.
+    pub fn get_texture_packs(&self) -> Vec<&TexturePack> {
+        self.texture_pack
+            .iter()
+            .chain(self.texture_packs.iter().flatten())
+            .collect()
+    }
+
+    /// Get the platform that the spec is
+    /// built for.
+    pub fn get_target(&self) -> Target {
+        self.target.unwrap_or_default()
+    }
+
+    /// Gets a build shell script, ready to be
+    /// written to disk.
+    ///
+    //  TODO: example
+    pub fn to_script(&self, repo_path: &Path) -> String {
+        let ccache_dir = self
+            .compiler
+            .as_ref()
+            .filter(|compiler| compiler.ccache)
+            .and_then(|compiler| compiler.ccache_dir.as_ref());
+
+        let mut env = match ccache_dir {
+            Some(dir) => format!(
+                "export CCACHE_DIR={}\n",
+                util::shell_quote(&dir.to_string_lossy())
+            ),
+            None => String::new(),
+        };
+
+        let wrappers = self
+            .compiler
+            .as_ref()
+            .map(|compiler| compiler.get_wrappers(self.get_target()))
+            .unwrap_or_default();
+
+        if !wrappers.is_empty() {
+            // the base directory holds the repo.
+            let full_repo_dir = get_full_repo_dir(repo_path);
+            let base_dir = full_repo_dir.parent().unwrap_or(&full_repo_dir);
+            let wrappers_dir = util::get_smbuilder_dir(base_dir).join("compiler");
+            let wrappers_dir = util::shell_quote(&wrappers_dir.to_string_lossy());
+
+            env.push_str(&format!("mkdir -p {}\n", wrappers_dir));
+            for (program, script) in wrappers {
+                let path = format!("{}/{}", wrappers_dir, util::shell_quote(&program));
+                env.push_str(&format!(
+                    "cat > {} <<'SMBUILDER_EOF'\n{}SMBUILDER_EOF\nchmod +x {}\n",
+                    path, script, path
+                ));
+            }
+            env.push_str(&format!(
+                "export SMBUILDER_PATH=\"$PATH\"\nexport PATH={}:\"$PATH\"\n",
+                wrappers_dir
+            ));
+        }
 
         format!(
             "#!/bin/sh
@@ -178,13 +279,29 @@ impl Spec {
 # DO NOT EDIT; YOUR CHANGES
 # WILL NOT BE SAVED.
 
-{} -C {} {} {} -j{}
+{}exec {}
         ",
-            make_cmd,
-            full_repo_dir.display(),
-            platform_makeopts,
-            makeopts_string,
-            jobs
+            env,
+            self.get_make_cmd(repo_path)
         )
     }
 }
+
+/// Get the absolute path of a repo.
+fn get_full_repo_dir(repo_path: &Path) -> PathBuf {
+    // the repo may not be cloned yet,
+    // when a build is only being planned.
+    let full_repo_dir = if repo_path.exists() {
+        fs::canonicalize(repo_path)
+    } else {
+        std::path::absolute(repo_path)
+    };
+
+    full_repo_dir.unwrap_or_else(|e| {
+        panic!(
+            "failed to get the absolute path from {}: {}",
+            &repo_path.display(),
+            e
+        )
+    })
+}
@@ -0,0 +1,405 @@
+/// Toolchain and dependency types.
+pub mod types;
+
+use crate::packs::archive::ArchiveKind;
+use crate::prelude::{Spec, Target};
+use types::*;
+
+use duct::cmd;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Directories to look for headers in
+/// when `pkg-config` is not available.
+const INCLUDE_DIRS: [&str; 4] = [
+    "/usr/include",
+    "/usr/local/include",
+    "/opt/homebrew/include",
+    "/opt/local/include",
+];
+
+// a macro to make the dependency
+// tables below less painful.
+macro_rules! packages {
+    ($debian:expr, $fedora:expr, $arch:expr, $opensuse:expr) => {
+        DistroPackages {
+            debian: $debian,
+            fedora: $fedora,
+            arch: $arch,
+            opensuse: $opensuse,
+        }
+    };
+}
+
+const MAKE: Dependency = Dependency {
+    name: "make",
+    kind: DependencyKind::Program(&["make", "gmake"]),
+    required: true,
+    packages: packages!("make", "make", "make", "make"),
+};
+
+const C_COMPILER: Dependency = Dependency {
+    name: "C compiler",
+    kind: DependencyKind::Program(&["cc", "gcc", "clang"]),
+    required: true,
+    packages: packages!("build-essential", "gcc", "base-devel", "gcc"),
+};
+
+const PYTHON3: Dependency = Dependency {
+    name: "python3",
+    kind: DependencyKind::Program(&["python3"]),
+    required: true,
+    packages: packages!("python3", "python3", "python", "python3"),
+};
+
+const PKG_CONFIG: Dependency = Dependency {
+    name: "pkg-config",
+    kind: DependencyKind::Program(&["pkg-config", "pkgconf"]),
+    required: true,
+    packages: packages!("pkg-config", "pkgconf-pkg-config", "pkgconf", "pkg-config"),
+};
+
+const SDL2: Dependency = Dependency {
+    name: "SDL2",
+    kind: DependencyKind::Library {
+        pkg_config: "sdl2",
+        header: "SDL2/SDL.h",
+    },
+    required: true,
+    packages: packages!("libsdl2-dev", "SDL2-devel", "sdl2", "libSDL2-devel"),
+};
+
+const GLEW: Dependency = Dependency {
+    name: "GLEW",
+    kind: DependencyKind::Library {
+        pkg_config: "glew",
+        header: "GL/glew.h",
+    },
+    required: true,
+    packages: packages!("libglew-dev", "glew-devel", "glew", "glew-devel"),
+};
+
+const AUDIOFILE: Dependency = Dependency {
+    name: "audiofile",
+    kind: DependencyKind::Library {
+        pkg_config: "audiofile",
+        header: "audiofile.h",
+    },
+    required: false,
+    packages: packages!(
+        "libaudiofile-dev",
+        "audiofile-devel",
+        "audiofile",
+        "audiofile-devel"
+    ),
+};
+
+const CCACHE: Dependency = Dependency {
+    name: "ccache",
+    kind: DependencyKind::Program(&["ccache"]),
+    required: true,
+    packages: packages!("ccache", "ccache", "ccache", "ccache"),
+};
+
+const MINGW_GCC: Dependency = Dependency {
+    name: "MinGW C compiler",
+    kind: DependencyKind::Program(&["x86_64-w64-mingw32-gcc"]),
+    required: true,
+    packages: packages!(
+        "gcc-mingw-w64-x86-64",
+        "mingw64-gcc",
+        "mingw-w64-gcc",
+        "mingw64-cross-gcc"
+    ),
+};
+
+const MINGW_GXX: Dependency = Dependency {
+    name: "MinGW C++ compiler",
+    kind: DependencyKind::Program(&["x86_64-w64-mingw32-g++"]),
+    required: true,
+    packages: packages!(
+        "g++-mingw-w64-x86-64",
+        "mingw64-gcc-c++",
+        "mingw-w64-gcc",
+        "mingw64-cross-gcc-c++"
+    ),
+};
+
+const UNZIP: Dependency = Dependency {
+    name: "unzip",
+    kind: DependencyKind::Program(&["unzip"]),
+    required: true,
+    packages: packages!("unzip", "unzip", "unzip", "unzip"),
+};
+
+const TAR: Dependency = Dependency {
+    name: "tar",
+    kind: DependencyKind::Program(&["tar", "bsdtar"]),
+    required: true,
+    packages: packages!("tar", "tar", "tar", "tar"),
+};
+
+// Debian doesn't package these, and
+// they're in the AUR on Arch Linux.
+const MINGW_SDL2: Dependency = Dependency {
+    name: "SDL2 (MinGW)",
+    kind: DependencyKind::Header(&[
+        "/usr/x86_64-w64-mingw32/include/SDL2/SDL.h",
+        "/usr/x86_64-w64-mingw32/sys-root/mingw/include/SDL2/SDL.h",
+        "/usr/local/x86_64-w64-mingw32/include/SDL2/SDL.h",
+    ]),
+    required: true,
+    packages: packages!("", "mingw64-SDL2", "", "mingw64-libSDL2-devel"),
+};
+
+const MINGW_GLEW: Dependency = Dependency {
+    name: "GLEW (MinGW)",
+    kind: DependencyKind::Header(&[
+        "/usr/x86_64-w64-mingw32/include/GL/glew.h",
+        "/usr/x86_64-w64-mingw32/sys-root/mingw/include/GL/glew.h",
+        "/usr/local/x86_64-w64-mingw32/include/GL/glew.h",
+    ]),
+    required: true,
+    packages: packages!("", "mingw64-glew", "", "mingw64-glew-devel"),
+};
+
+/// Get the dependencies that are needed to
+/// build a spec, or the dependencies of a
+/// vanilla sm64ex build if there is no spec.
+pub fn get_dependencies(spec: Option<&Spec>) -> Vec<Dependency> {
+    let target = spec.map(|s| s.get_target()).unwrap_or_default();
+
+    // the native C compiler is still
+    // needed to build the tools that
+    // extract the assets.
+    let mut dependencies = vec![MAKE, C_COMPILER, PYTHON3, PKG_CONFIG];
+
+    match target {
+        Target::Native => dependencies.push(SDL2),
+        Target::MinGW64 => dependencies.extend([MINGW_GCC, MINGW_GXX, MINGW_SDL2]),
+    }
+
+    // GLEW is only used by the OpenGL renderer,
+    // which is what the default makeopts pick.
+    let render_api = spec
+        .and_then(|s| s.makeopts.as_ref())
+        .and_then(|makeopts| makeopts.iter().rev().find(|m| m.key == "RENDER_API"))
+        .map(|m| m.value.as_str())
+        .unwrap_or("GL");
+
+    if render_api.starts_with("GL") {
+        match target {
+            Target::Native => dependencies.push(GLEW),
+            Target::MinGW64 => dependencies.push(MINGW_GLEW),
+        }
+    }
+
+    // the audio tools bundle their own copy on
+    // newer ports, but older ones need it installed.
+    dependencies.push(AUDIOFILE);
+
+    let uses_ccache = spec
+        .and_then(|s| s.compiler.as_ref())
+        .map(|c| c.ccache)
+        .unwrap_or(false);
+
+    if uses_ccache {
+        dependencies.push(CCACHE);
+    }
+
+    // packs can come in archives,
+    // which are read with the
+    // programs for them.
+    let archive_kinds = spec.map(get_archive_kinds).unwrap_or_default();
+
+    if archive_kinds.contains(&ArchiveKind::Zip) {
+        dependencies.push(UNZIP);
+    }
+
+    if archive_kinds.contains(&ArchiveKind::Tar) {
+        dependencies.push(TAR);
+    }
+
+    dependencies
+}
+
+/// Get the kinds of the archives
+/// that the packs of a spec are in.
+fn get_archive_kinds(spec: &Spec) -> Vec<ArchiveKind> {
+    let texture_packs = spec.get_texture_packs().into_iter().map(|p| &p.path);
+    let sound_packs = spec.sound_packs.iter().flatten().map(|p| &p.path);
+    let language_packs = spec.language_packs.iter().flatten().map(|p| &p.path);
+    let model_packs = spec.model_packs.iter().flatten().map(|p| &p.path);
+
+    texture_packs
+        .chain(sound_packs)
+        .chain(language_packs)
+        .chain(model_packs)
+        .filter_map(ArchiveKind::from_path)
+        .collect()
+}
+
+/// Detect the Linux distribution
+/// from `/etc/os-release`.
+pub fn detect_distro() -> Distro {
+    let os_release = match fs::read_to_string("/etc/os-release") {
+        Ok(s) => s,
+        Err(_) => return Distro::Unknown,
+    };
+
+    // `ID_LIKE` lists the parent distros,
+    // such as `ubuntu debian` on Mint.
+    let ids = os_release
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("ID=")
+                .or_else(|| line.strip_prefix("ID_LIKE="))
+        })
+        .flat_map(|value| value.trim_matches('"').split_whitespace())
+        .collect::<Vec<&str>>();
+
+    for id in ids {
+        match id {
+            "debian" | "ubuntu" => return Distro::Debian,
+            "fedora" | "rhel" | "centos" => return Distro::Fedora,
+            "arch" => return Distro::Arch,
+            "opensuse" | "suse" | "opensuse-leap" | "opensuse-tumbleweed" => {
+                return Distro::OpenSuse
+            }
+            _ => (),
+        }
+    }
+
+    Distro::Unknown
+}
+
+/// Find a program on the `PATH`,
+/// like `which` would, skipping
+/// files that can't be run.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| {
+            fs::metadata(candidate)
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+}
+
+/// Get the first line of the output
+/// of `program --version`.
+fn get_program_version(program: &Path) -> Option<String> {
+    let output = cmd!(program, "--version")
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .ok()?;
+
+    // some programs print their
+    // version to stderr instead.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_owned())
+}
+
+fn probe_program(dependency: Dependency, names: &[&str]) -> ProbeResult {
+    let path = names.iter().find_map(|name| find_program(name));
+    let version = path.as_deref().and_then(get_program_version);
+
+    ProbeResult {
+        dependency,
+        found: path.is_some(),
+        version,
+        path,
+    }
+}
+
+fn probe_library(
+    dependency: Dependency,
+    pkg_config: &str,
+    header: &str,
+    pkg_config_path: Option<&Path>,
+) -> ProbeResult {
+    if let Some(pkg_config_path) = pkg_config_path {
+        let output = cmd!(pkg_config_path, "--modversion", pkg_config)
+            .stdout_capture()
+            .stderr_null()
+            .unchecked()
+            .run();
+
+        if let Ok(output) = output {
+            if output.status.success() {
+                let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+                return ProbeResult {
+                    dependency,
+                    found: true,
+                    version: Some(version),
+                    path: None,
+                };
+            }
+        }
+    }
+
+    // pkg-config is either missing, or doesn't know
+    // about the library (such as on macOS, or when it
+    // was built from source), so look for the header.
+    let header_path = INCLUDE_DIRS
+        .iter()
+        .map(|dir| Path::new(dir).join(header))
+        .find(|path| path.is_file());
+
+    ProbeResult {
+        dependency,
+        found: header_path.is_some(),
+        version: None,
+        path: header_path,
+    }
+}
+
+fn probe_header(dependency: Dependency, paths: &[&str]) -> ProbeResult {
+    let path = paths.iter().map(PathBuf::from).find(|path| path.is_file());
+
+    ProbeResult {
+        dependency,
+        found: path.is_some(),
+        version: None,
+        path,
+    }
+}
+
+/// Check the system for the toolchain and
+/// libraries that are needed to build a spec
+/// (or a vanilla sm64ex build, if there is none).
+///
+// TODO: example
+pub fn probe(spec: Option<&Spec>) -> ToolchainReport {
+    let pkg_config_path = find_program("pkg-config").or_else(|| find_program("pkgconf"));
+
+    let results = get_dependencies(spec)
+        .into_iter()
+        .map(|dependency| match dependency.kind {
+            DependencyKind::Program(names) => probe_program(dependency, names),
+            DependencyKind::Library { pkg_config, header } => {
+                probe_library(dependency, pkg_config, header, pkg_config_path.as_deref())
+            }
+            DependencyKind::Header(paths) => probe_header(dependency, paths),
+        })
+        .collect();
+
+    ToolchainReport {
+        distro: detect_distro(),
+        results,
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A Linux distribution (or
+/// family of distributions), used
+/// to suggest package names.
+pub enum Distro {
+    /// Debian, Ubuntu and
+    /// their derivatives.
+    Debian,
+    /// Fedora, RHEL and
+    /// their derivatives.
+    Fedora,
+    /// Arch Linux and its
+    /// derivatives.
+    Arch,
+    /// openSUSE.
+    OpenSuse,
+    /// Anything else.
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How a dependency is found
+/// on the system.
+pub enum DependencyKind {
+    /// A program, looked up on the `PATH`.
+    /// Any of the listed names will do.
+    Program(&'static [&'static str]),
+    /// A library, looked up with `pkg-config`
+    /// under the given module name, or by
+    /// its header if `pkg-config` is missing.
+    Library {
+        /// The `pkg-config` module name.
+        pkg_config: &'static str,
+        /// A header to look for, relative
+        /// to the include directories.
+        header: &'static str,
+    },
+    /// A header at a fixed path, such as in
+    /// the sysroot of a cross toolchain. Any
+    /// of the listed paths will do.
+    Header(&'static [&'static str]),
+}
+
+#[derive(Clone, Copy, Debug)]
+/// The package that provides a
+/// dependency, on the common
+/// Linux distributions.
+///
+/// An empty string means that the
+/// distro doesn't package it.
+pub struct DistroPackages {
+    /// The Debian/Ubuntu package.
+    pub debian: &'static str,
+    /// The Fedora package.
+    pub fedora: &'static str,
+    /// The Arch Linux package.
+    pub arch: &'static str,
+    /// The openSUSE package.
+    pub opensuse: &'static str,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// A dependency that is
+/// needed to build a port.
+pub struct Dependency {
+    /// A human readable name.
+    pub name: &'static str,
+    /// How to find the dependency.
+    pub kind: DependencyKind,
+    /// Whether the build cannot
+    /// work without it.
+    pub required: bool,
+    /// The packages that provide it.
+    pub packages: DistroPackages,
+}
+
+#[derive(Clone, Debug)]
+/// The result of looking for
+/// a dependency on the system.
+pub struct ProbeResult {
+    /// The dependency that
+    /// was looked for.
+    pub dependency: Dependency,
+    /// Whether it was found.
+    pub found: bool,
+    /// The version that was
+    /// found, if it could be
+    /// determined.
+    pub version: Option<String>,
+    /// The program or header that
+    /// was found, if applicable.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug)]
+/// A report on the dependencies
+/// of a build, made by `toolchain::probe`.
+pub struct ToolchainReport {
+    /// The distribution that the
+    /// system is running.
+    pub distro: Distro,
+    /// The results, one for
+    /// each dependency.
+    pub results: Vec<ProbeResult>,
+}
+
+impl DistroPackages {
+    /// Get the package for a distro.
+    ///
+    /// Returns `None` on an unknown distro,
+    /// or if the distro doesn't package it.
+    pub fn get(&self, distro: Distro) -> Option<&'static str> {
+        use Distro::*;
+
+        let package = match distro {
+            Debian => self.debian,
+            Fedora => self.fedora,
+            Arch => self.arch,
+            OpenSuse => self.opensuse,
+            Unknown => return None,
+        };
+
+        if package.is_empty() {
+            return None;
+        }
+
+        Some(package)
+    }
+}
+
+impl ToolchainReport {
+    /// Gets the required dependencies
+    /// that were not found.
+    pub fn missing(&self) -> Vec<&ProbeResult> {
+        self.results
+            .iter()
+            .filter(|r| !r.found && r.dependency.required)
+            .collect()
+    }
+
+    /// Gets the optional dependencies
+    /// that were not found.
+    pub fn missing_optional(&self) -> Vec<&ProbeResult> {
+        self.results
+            .iter()
+            .filter(|r| !r.found && !r.dependency.required)
+            .collect()
+    }
+
+    /// Checks if every required
+    /// dependency was found.
+    pub fn is_ok(&self) -> bool {
+        self.missing().is_empty()
+    }
+
+    /// Gets a command that installs every
+    /// missing dependency (required or not)
+    /// on the detected distro.
+    ///
+    /// Returns `None` if nothing is missing,
+    /// or if the distro is unknown.
+    pub fn install_command(&self) -> Option<String> {
+        use Distro::*;
+
+        // one package may provide
+        // more than one dependency.
+        let mut packages: Vec<&str> = Vec::new();
+        for result in self.results.iter().filter(|r| !r.found) {
+            if let Some(package) = result.dependency.packages.get(self.distro) {
+                if !packages.contains(&package) {
+                    packages.push(package);
+                }
+            }
+        }
+
+        if packages.is_empty() {
+            return None;
+        }
+
+        let install_cmd = match self.distro {
+            Debian => "sudo apt install",
+            Fedora => "sudo dnf install",
+            Arch => "sudo pacman -S",
+            OpenSuse => "sudo zypper install",
+            Unknown => return None,
+        };
+
+        Some(format!("{} {}", install_cmd, packages.join(" ")))
+    }
+}
+
+impl fmt::Display for Distro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Distro::*;
+
+        let result = match self {
+            Debian => "Debian/Ubuntu",
+            Fedora => "Fedora",
+            Arch => "Arch Linux",
+            OpenSuse => "openSUSE",
+            Unknown => "an unknown distro",
+        };
+
+        write!(f, "{}", result)
+    }
+}
@@ -8,16 +8,13 @@ use crate::{c_fs, prelude::*};
 use std::{
     fmt::Debug,
     fs,
-    io::{self, BufWriter, Write},
+    io::{BufWriter, Write},
     path::Path,
 };
 
-use fs_extra::dir::CopyOptions;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-extern crate fs_extra;
-
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 /// Represents the region of a given ROM file.
@@ -33,6 +30,20 @@ pub enum Region {
     Sh,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+/// The platform that a build runs on.
+pub enum Target {
+    #[default]
+    #[serde(rename = "native")]
+    /// The platform that
+    /// smbuilder runs on.
+    Native,
+    #[serde(rename = "x86_64-w64-mingw32")]
+    /// 64-bit Windows, cross
+    /// compiled with MinGW.
+    MinGW64,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// Represents a ROM file.
 pub struct Rom {
@@ -82,14 +93,29 @@ pub struct Repo {
     pub url: String,
     /// The branch to clone from.
     pub branch: String,
+    /// A revision (such as a commit hash or
+    /// a tag) to pin the repo to, instead of
+    /// the tip of the branch.
+    pub rev: Option<String>,
     /// The description of what the
     /// repo is, useful for launchers.
     pub about: String,
     /// Does this repo support DynOS packs?
     pub supports_dynos: bool,
+    /// Does this repo load sm64ex-coop
+    /// mods, from the `mods` directory?
+    #[serde(default)]
+    pub supports_coop_mods: bool,
+    /// The name of the executable that the
+    /// port produces, relative to the build
+    /// directory (`build/{region}_pc`).
+    ///
+    /// Will be looked for in the build
+    /// directory if it is not set.
+    pub executable: Option<PathBuf>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 /// Represents a key-value pair
 /// Make Flag, such as `BETTERCAMERA=1`
 pub struct Makeopt {
@@ -100,6 +126,46 @@ pub struct Makeopt {
     pub value: String,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Overrides for the compiler that
+/// builds the port.
+///
+/// Make flags such as `CC` would be passed
+/// on to the makefiles of the port's host
+/// tools too, which can't be built with a
+/// cross compiler. Instead, the build script
+/// puts wrappers in front of the compilers
+/// that the port runs, `$(CROSS)gcc` and
+/// `$(CROSS)g++`, which add the overrides.
+///
+// TODO: example
+pub struct CompilerConfig {
+    /// The C compiler, such
+    /// as `gcc` or `clang`.
+    pub cc: Option<String>,
+
+    /// The C++ compiler, such
+    /// as `g++` or `clang++`.
+    pub cxx: Option<String>,
+
+    /// Extra flags for the compilers.
+    pub cflags: Option<String>,
+
+    /// Extra flags for the linker.
+    pub ldflags: Option<String>,
+
+    /// Whether to run the compilers
+    /// through `ccache`.
+    #[serde(default)]
+    pub ccache: bool,
+
+    /// The cache directory for `ccache`,
+    /// which can be shared between
+    /// instances. `ccache` picks its
+    /// own if it is not set.
+    pub ccache_dir: Option<PathBuf>,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 /// Represents a patch.
 pub struct Patch {
@@ -126,6 +192,72 @@ pub struct TexturePack {
     pub path: PathBuf,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Represents a Sound Pack, with
+/// a `sound` directory that goes
+/// into `res/sound`.
+pub struct SoundPack {
+    /// The name (label) of
+    /// the sound pack, for
+    /// use with launchers.
+    pub name: String,
+
+    /// The location of the
+    /// sound pack on disk.
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Represents a Language Pack, with
+/// a `texts` directory of translations
+/// that goes into `res/texts`.
+pub struct LanguagePack {
+    /// The name (label) of
+    /// the language pack, for
+    /// use with launchers.
+    pub name: String,
+
+    /// The location of the
+    /// language pack on disk.
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Represents an sm64ex-coop Lua mod,
+/// which is either a single `.lua` file,
+/// or a directory with a `main.lua`.
+pub struct CoopMod {
+    /// The name (label) of
+    /// the mod, for use
+    /// with launchers.
+    pub name: String,
+
+    /// The location of the
+    /// mod on disk.
+    pub path: PathBuf,
+
+    /// Whether the mod is installed
+    /// disabled, so that the game
+    /// doesn't load it.
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// Represents a Model Pack, in
+/// the layout that render96ex
+/// ships its models in.
+pub struct ModelPack {
+    /// The name (label) of
+    /// the model pack, for
+    /// use with launchers.
+    pub name: String,
+
+    /// The location of the
+    /// model pack on disk.
+    pub path: PathBuf,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 /// A structure to represent
 /// a DynOS (Dynamic Options
@@ -147,6 +279,12 @@ pub struct DynosPack {
     /// The location of
     /// the pack, on disk.
     pub path: PathBuf,
+
+    /// Whether the pack is installed
+    /// disabled, so that DynOS
+    /// doesn't load it.
+    #[serde(default)]
+    pub disabled: bool,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -171,6 +309,94 @@ pub struct PostBuildScript {
     pub path: Option<PathBuf>,
 }
 
+impl Target {
+    /// Get the target triple of a cross
+    /// target, or `None` if it's native.
+    pub fn get_triple(&self) -> Option<&'static str> {
+        match self {
+            Target::Native => None,
+            Target::MinGW64 => Some("x86_64-w64-mingw32"),
+        }
+    }
+
+    /// Get the prefix of the cross toolchain's
+    /// programs, such as `x86_64-w64-mingw32-`.
+    ///
+    /// It is empty for the native target.
+    pub fn get_cross_prefix(&self) -> String {
+        match self.get_triple() {
+            Some(triple) => format!("{}-", triple),
+            None => String::new(),
+        }
+    }
+}
+
+impl CompilerConfig {
+    /// Checks if any of the
+    /// overrides are set.
+    pub fn has_overrides(&self) -> bool {
+        self.cc.is_some()
+            || self.cxx.is_some()
+            || self.cflags.is_some()
+            || self.ldflags.is_some()
+            || self.ccache
+    }
+
+    /// Get the wrappers of the compilers that
+    /// the port runs, when building for `target`,
+    /// as pairs of the program that they stand in
+    /// for and the shell script that runs it.
+    ///
+    /// The wrappers run the compilers with
+    /// `$SMBUILDER_PATH` as the `PATH`, so that
+    /// they don't find themselves. The linker
+    /// flags are only added when linking, which
+    /// the ports do with the compilers too.
+    pub fn get_wrappers(&self, target: Target) -> Vec<(String, String)> {
+        if !self.has_overrides() {
+            return Vec::new();
+        }
+
+        let wrapper = if self.ccache { "ccache " } else { "" };
+        let cflags = match &self.cflags {
+            Some(cflags) => format!(" {}", cflags),
+            None => String::new(),
+        };
+        let ldflags = match &self.ldflags {
+            Some(ldflags) => format!(" {}", ldflags),
+            None => String::new(),
+        };
+
+        let cross_prefix = target.get_cross_prefix();
+        let compilers = [
+            (format!("{}gcc", cross_prefix), &self.cc),
+            (format!("{}g++", cross_prefix), &self.cxx),
+        ];
+
+        compilers
+            .into_iter()
+            .map(|(program, compiler)| {
+                let compiler = compiler.clone().unwrap_or_else(|| program.clone());
+                let run = format!("exec {}{}{} \"$@\"", wrapper, compiler, cflags);
+
+                let script = format!(
+                    "#!/bin/sh
+PATH=\"$SMBUILDER_PATH\"
+
+case \" $* \" in
+    *\" -c \"*|*\" -S \"*|*\" -E \"*|*\" -M \"*|*\" -MM \"*) {} ;;
+    *) {}{} ;;
+esac
+",
+                    run, run, ldflags
+                );
+
+                (program, script)
+            })
+            .collect()
+    }
+}
+
 impl Makeopt {
     /// Creates a new `Makeopt`.
     ///
@@ -187,6 +413,16 @@ impl Makeopt {
     /// current OS.
     // TODO: example
     pub fn default_makeopts() -> Vec<Self> {
+        Self::default_makeopts_for(Target::Native)
+    }
+
+    /// Get the make flags that are passed
+    /// to every build for a target.
+    ///
+    /// Cross targets don't get the make
+    /// flags of the platform smbuilder
+    /// runs on.
+    pub fn default_makeopts_for(target: Target) -> Vec<Self> {
         let mut makeopts: Vec<Makeopt> = Vec::new();
 
         // make a macro to make life easier
@@ -205,18 +441,28 @@ impl Makeopt {
         push_makeopt!("AUDIO_API", "SDL2");
         push_makeopt!("CONTROLLER_API", "SDL2");
 
-        // macOS stuff
-        #[cfg(target_os = "macos")]
-        {
-            push_makeopt!("OSX_BUILD", "1");
-            push_makeopt!("TARGET_BITS", "64");
+        match target {
+            Target::Native => {
+                // macOS stuff
+                #[cfg(target_os = "macos")]
+                {
+                    push_makeopt!("OSX_BUILD", "1");
+                    push_makeopt!("TARGET_BITS", "64");
 
-            #[cfg(target_arch = "x86_64")]
-            push_makeopt!("TARGET_ARCH", "x86_64-apple-darwin");
+                    #[cfg(target_arch = "x86_64")]
+                    push_makeopt!("TARGET_ARCH", "x86_64-apple-darwin");
 
-            #[cfg(target_arch = "aarch64")]
-            push_makeopt!("TARGET_ARCH", "aarch64-apple-darwin");
-        };
+                    #[cfg(target_arch = "aarch64")]
+                    push_makeopt!("TARGET_ARCH", "aarch64-apple-darwin");
+                };
+            }
+            Target::MinGW64 => {
+                push_makeopt!("WINDOWS_BUILD", "1");
+                push_makeopt!("TARGET_BITS", "64");
+                push_makeopt!("TARGET_ARCH", "x86_64-w64-mingw32");
+                push_makeopt!("CROSS", "x86_64-w64-mingw32-");
+            }
+        }
 
         makeopts
     }
@@ -303,154 +549,6 @@ impl PostBuildScript {
     }
 }
 
-impl DynosPack {
-    /// Creates a new DynOS pack.
-    ///
-    // TODO: example
-    pub fn new<S, P>(name: S, path: P) -> Self
-    where
-        S: ToString,
-        P: Into<PathBuf>,
-    {
-        DynosPack {
-            name: name.to_string(),
-            path: path.into(),
-        }
-    }
-
-    /// Installs the DynOS pack (copies it
-    /// into the correct location)
-    ///
-    // TODO: example
-    pub fn install<P: AsRef<Path>>(
-        &self,
-        spec: &Spec,
-        repo_dir: P,
-        callbacks: &mut Callbacks,
-    ) -> BuilderResult<()> {
-        if !spec.repo.supports_dynos {
-            run_callback!(
-                callbacks.log_cb,
-                types::LogType::Warn,
-                "this build does not support DynOS packs. stopping."
-            );
-            return Ok(());
-        }
-
-        let target_path = repo_dir
-            .as_ref()
-            .join("build")
-            .join(format!("{}_pc", spec.rom.region.to_string()))
-            .join("dynos")
-            .join("packs");
-
-        match fs_extra::dir::copy(&self.path, &target_path, &CopyOptions::new()) {
-            Ok(_) => (),
-            Err(e) => {
-                let msg = format!(
-                    "whilst copying the DynOS pack from {} to {}: {}",
-                    &self.path.display(),
-                    &target_path.display(),
-                    e
-                );
-                let err = err!(c_fs!(e, msg), "failed to copy the DynOS pack");
-                return Err(err);
-            }
-        };
-
-        Ok(())
-    }
-
-    /// Permanently removes the pack
-    /// from disk, effectively uninstalling
-    /// it.
-    pub fn remove<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) {
-        let pack_filename = self
-            .path
-            .iter()
-            .last()
-            .expect("the DynOS pack should have a filename!");
-
-        let target_path = repo_dir
-            .as_ref()
-            .join("build")
-            .join(format!("{}_pc", spec.rom.region.to_string()))
-            .join("dynos")
-            .join("packs")
-            .join(pack_filename);
-
-        fs_extra::dir::remove(target_path)
-            .unwrap_or_else(|e| panic!("failed to remove the directory: {}", e));
-    }
-}
-
-impl TexturePack {
-    /// Creates a new TexturePack.
-    ///
-    // TODO: example
-    pub fn new<S, P>(name: S, path: P) -> Self
-    where
-        S: ToString,
-        P: Into<PathBuf>,
-    {
-        TexturePack {
-            name: name.to_string(),
-            path: path.into(),
-        }
-    }
-
-    /// Installs the Texture pack (copies
-    /// it into the correct location)
-    ///
-    // TODO: example
-    pub fn install<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) -> Result<(), Error> {
-        let target_path = repo_dir
-            .as_ref()
-            .join("build")
-            .join(format!("{}_pc", spec.rom.region.to_string()))
-            .join("res");
-        //.join("gfx")
-        // {repo_dir}/build/{region}_pc/res/gfx
-
-        let pack_path = &self.path.join("gfx");
-
-        if !pack_path.exists() {
-            let inner_err = io::Error::new(
-                io::ErrorKind::NotFound,
-                "could not find the gfx directory in the texture pack path!",
-            );
-
-            let err = err!(c_fs!(inner_err), "invalid texture pack"); // TODO:
-            return Err(err);
-        };
-
-        fs_extra::dir::copy(pack_path, &target_path, &CopyOptions::new()).unwrap_or_else(|e| {
-            panic!(
-                "failed to copy the texture pack from {} to {}: {}",
-                &pack_path.display(),
-                &target_path.display(),
-                e
-            )
-        });
-
-        Ok(())
-    }
-
-    /// Permanently removes the texture
-    /// pack from disk, effectively
-    /// uninstalling it.
-    pub fn remove<P: AsRef<Path>>(&self, spec: &Spec, repo_dir: P) {
-        let target_path = repo_dir
-            .as_ref()
-            .join("build")
-            .join(format!("{}_pc", spec.rom.region.to_string()))
-            .join("res")
-            .join("gfx");
-
-        fs_extra::dir::remove(target_path)
-            .unwrap_or_else(|e| panic!("could not find the texture pack to remove: {}", e));
-    }
-}
 /*
 impl Patch {
     fn new<S: ToString, P: Into<PathBuf>>(name: S, path: P) -> Self {
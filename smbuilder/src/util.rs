@@ -1,5 +1,10 @@
 use crate::prelude::*;
-use std::{fs, os::unix::prelude::PermissionsExt, path::Path};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    os::unix::prelude::PermissionsExt,
+    path::{Path, PathBuf},
+};
 
 #[macro_export]
 macro_rules! try_or_return {
@@ -17,7 +22,7 @@ macro_rules! try_or_return {
 pub fn get_makeopts_string(makeopts: &[Makeopt]) -> String {
     let mut result = makeopts
         .iter()
-        .map(|makeopt| format!("{}={}", makeopt.key, makeopt.value))
+        .map(|makeopt| format!("{}={}", makeopt.key, shell_quote(&makeopt.value)))
         .collect::<Vec<String>>()
         .join(" ");
 
@@ -25,6 +30,21 @@ pub fn get_makeopts_string(makeopts: &[Makeopt]) -> String {
     result
 }
 
+/// Quote a string for a bourne shell, if
+/// it has anything other than plain words,
+/// paths and flags in it.
+pub fn shell_quote(s: &str) -> String {
+    let is_plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+
+    if is_plain {
+        s.to_owned()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
 /// Make a file executable.
 /// Equivalent to `chmod +x`.
 pub fn make_file_executable(path: &Path) {
@@ -39,7 +59,7 @@ pub fn make_file_executable(path: &Path) {
     fs::set_permissions(
         path,
         fs::Permissions::from_mode(
-            file_metadata.permissions().mode() + 0o111, // equivalent of a chmod +x.
+            file_metadata.permissions().mode() | 0o111, // equivalent of a chmod +x.
         ),
     )
     .unwrap_or_else(|e| {
@@ -50,3 +70,75 @@ pub fn make_file_executable(path: &Path) {
         )
     });
 }
+
+/// Get the directory where smbuilder keeps
+/// its own state for an instance, which is
+/// `{base_dir}/.smbuilder`.
+pub fn get_smbuilder_dir<P: AsRef<Path>>(base_dir: P) -> PathBuf {
+    base_dir.as_ref().join(".smbuilder")
+}
+
+/// Get the SHA-256 hash of a file,
+/// as a hex string.
+pub fn hash_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Get the SHA-256 hash of a
+/// string, as a hex string.
+pub fn hash_str<S: AsRef<str>>(s: S) -> String {
+    format!("{:x}", Sha256::digest(s.as_ref().as_bytes()))
+}
+
+/// Get every file in a directory and its
+/// subdirectories, relative to it, sorted.
+///
+/// Linked directories aren't followed,
+/// and are listed like files.
+pub fn get_files_recursive<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(current_dir) = dirs.pop() {
+        for entry in fs::read_dir(&current_dir)? {
+            let path = entry?.path();
+
+            // a linked directory may lead
+            // outside of `dir`, or loop.
+            if fs::symlink_metadata(&path)?.is_dir() {
+                dirs.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_plain_words_unquoted() {
+        assert_eq!(shell_quote("gcc"), "gcc");
+        assert_eq!(shell_quote("-DFOO=1"), "-DFOO=1");
+        assert_eq!(shell_quote("/usr/bin/ccache"), "/usr/bin/ccache");
+    }
+
+    #[test]
+    fn quotes_everything_else() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}